@@ -1,10 +1,121 @@
-use std::path::Path;
+use std::{fmt::Display, path::Path, str::FromStr};
 
-use serde::Serialize;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
 
-use crate::{args::ConfigArgs, profile::Profile};
+use crate::{
+    args::{ConfigArgs, OutputFormat},
+    auth::{self, TokenFile},
+    encryption::EncryptedToken,
+    env::EnvConfig,
+    import::DEFAULT_IMPORT_STATE_FILENAME,
+    outbox::DEFAULT_OUTBOX_FILENAME,
+    profile::{Profile, DEFAULT_PROFILE_NAME},
+    project::ProjectConfig,
+    sync::DEFAULT_SYNC_STATE_FILENAME,
+    utils::date::{date_value::DateValue, zone::Zone},
+    web_client::local::DEFAULT_STORE_FILENAME,
+};
 
 pub const DEFAULT_API_KEY_FILENAME: &str = "api_key";
+const DEFAULT_OAUTH_DEVICE_PATH: &str = "/oauth/device_authorization";
+const DEFAULT_OAUTH_TOKEN_PATH: &str = "/oauth/token";
+
+/// Files kept next to each profile, moved and deleted together with it
+pub const PROFILE_FILENAMES: &[&str] = &[
+    DEFAULT_API_KEY_FILENAME,
+    DEFAULT_OUTBOX_FILENAME,
+    DEFAULT_STORE_FILENAME,
+    DEFAULT_SYNC_STATE_FILENAME,
+    DEFAULT_IMPORT_STATE_FILENAME,
+];
+
+/// Where notes are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Notes live on the jot server
+    #[default]
+    Web,
+    /// Notes live in a file next to the profile, no server needed
+    Local,
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "web" => Ok(Backend::Web),
+            "local" => Ok(Backend::Local),
+            _ => anyhow::bail!("Unknown backend '{}', use 'web' or 'local'", s),
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Web => write!(f, "web"),
+            Backend::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// How `jot login` obtains a token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthFlowKind {
+    /// Device code generated by jot and confirmed on the jot server
+    #[default]
+    Jot,
+    /// OAuth 2.0 Device Authorization Grant (RFC 8628) against an identity provider
+    Oauth,
+}
+
+impl FromStr for AuthFlowKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jot" => Ok(AuthFlowKind::Jot),
+            "oauth" => Ok(AuthFlowKind::Oauth),
+            _ => anyhow::bail!("Unknown auth flow '{}', use 'jot' or 'oauth'", s),
+        }
+    }
+}
+
+impl Display for AuthFlowKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthFlowKind::Jot => write!(f, "jot"),
+            AuthFlowKind::Oauth => write!(f, "oauth"),
+        }
+    }
+}
+
+/// Where a configuration value came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Arg,
+    Env,
+    Project,
+    Profile,
+    Default,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Arg => write!(f, "arg"),
+            Origin::Env => write!(f, "env"),
+            Origin::Project => write!(f, "project"),
+            Origin::Profile => write!(f, "profile"),
+            Origin::Default => write!(f, "default"),
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct AppConfig {
@@ -12,11 +123,45 @@ pub struct AppConfig {
     pub mock_server: bool,
     #[cfg(debug_assertions)]
     pub mock_param: Option<String>,
+    pub backend: Backend,
     pub server_url: String,
+    pub timezone: Zone,
     pub profile_path: String,
+    /// Files merged into this config, from the lowest precedence to the highest
+    pub layers: Vec<String>,
     pub api_key_path: String,
+    pub api_key_command: Option<String>,
+    pub api_key_store_command: Option<String>,
+    pub outbox_path: String,
+    pub store_path: String,
+    pub sync_state_path: String,
+    pub import_state_path: String,
+    pub default_tags: Vec<String>,
+    pub default_output: OutputFormat,
+    pub default_limit: Option<i64>,
+    #[serde(serialize_with = "serialize_date_value")]
+    pub default_date: Option<DateValue>,
     pub profile_exists: bool,
     pub token: Option<String>,
+    /// Encrypt the api key file written by `jot login` with a passphrase
+    pub encrypt_token: bool,
+    /// Whether the api key file read for this run is encrypted
+    pub token_encrypted: bool,
+    /// Encrypted api key file, kept locked until a command needs the token
+    #[serde(skip)]
+    pub encrypted_token: Option<EncryptedToken>,
+    /// Passphrase that unlocked the api key file, reused when a new token is saved
+    #[serde(skip)]
+    pub passphrase: Option<String>,
+    pub auth_flow: AuthFlowKind,
+    pub oauth_client_id: Option<String>,
+    /// Device authorization endpoint of the identity provider
+    pub oauth_device_url: String,
+    pub oauth_token_url: String,
+    pub oauth_scope: Option<String>,
+    /// Where the configurable values above came from, in field order
+    #[serde(skip)]
+    pub origins: Vec<(&'static str, Origin)>,
 }
 
 impl Default for AppConfig {
@@ -24,28 +169,255 @@ impl Default for AppConfig {
         AppConfig {
             #[cfg(debug_assertions)]
             mock_server: false,
+            #[cfg(debug_assertions)]
             mock_param: None,
+            backend: Backend::Web,
             server_url: "http://localhost:9000".to_string(),
+            timezone: Zone::Local,
             profile_path: "./".to_string(),
+            layers: vec![],
             api_key_path: format!("./{}", DEFAULT_API_KEY_FILENAME),
+            api_key_command: None,
+            api_key_store_command: None,
+            outbox_path: format!("./{}", DEFAULT_OUTBOX_FILENAME),
+            store_path: format!("./{}", DEFAULT_STORE_FILENAME),
+            sync_state_path: format!("./{}", DEFAULT_SYNC_STATE_FILENAME),
+            import_state_path: format!("./{}", DEFAULT_IMPORT_STATE_FILENAME),
+            default_tags: vec![],
+            default_output: OutputFormat::Pretty,
+            default_limit: None,
+            default_date: None,
             profile_exists: false,
             token: None,
+            encrypt_token: false,
+            token_encrypted: false,
+            encrypted_token: None,
+            passphrase: None,
+            auth_flow: AuthFlowKind::Jot,
+            oauth_client_id: None,
+            oauth_device_url: format!("http://localhost:9000{}", DEFAULT_OAUTH_DEVICE_PATH),
+            oauth_token_url: format!("http://localhost:9000{}", DEFAULT_OAUTH_TOKEN_PATH),
+            oauth_scope: None,
+            origins: vec![],
         }
     }
 }
 
 impl AppConfig {
-    pub fn from_args(args: ConfigArgs, profile_path: &Path, profile: Option<&Profile>) -> Self {
+    /// Merges the layers in order of precedence: arguments, `JOT_*` environment variables,
+    /// project `.jot.toml`, profile, defaults
+    pub fn from_args(
+        args: ConfigArgs,
+        env: &EnvConfig,
+        profile_path: &Path,
+        profile: Option<&Profile>,
+        project: Option<&ProjectConfig>,
+    ) -> anyhow::Result<Self> {
         let defaults = AppConfig::default();
+        let arg_origin = |id: &str| args.origins.get(id).copied().unwrap_or(Origin::Arg);
+
+        let (server_url, server_url_origin) = layered(
+            [
+                (args.server_url.clone(), arg_origin("server_url")),
+                (env.server_url.clone(), Origin::Env),
+                (profile.and_then(|p| p.server_url.clone()), Origin::Profile),
+            ],
+            defaults.server_url,
+        );
+        let (backend, backend_origin) = layered(
+            [
+                (env.backend, Origin::Env),
+                (profile.and_then(|p| p.backend), Origin::Profile),
+            ],
+            defaults.backend,
+        );
+
+        let project_timezone = project
+            .and_then(|p| p.timezone.as_deref())
+            .map(str::parse::<Zone>)
+            .transpose()
+            .context("Invalid timezone in .jot.toml")?;
+        let profile_timezone = profile
+            .and_then(|p| p.timezone.as_deref())
+            .map(str::parse::<Zone>)
+            .transpose()
+            .context("Invalid timezone in profile")?;
+        let (timezone, timezone_origin) = layered(
+            [
+                (env.timezone, Origin::Env),
+                (project_timezone, Origin::Project),
+                (profile_timezone, Origin::Profile),
+            ],
+            defaults.timezone,
+        );
 
-        let profile_server_url = profile.and_then(|p| p.server_url.as_ref());
-        let api_key_path = profile
-            .and_then(|p| p.api_key_path.as_ref())
-            .cloned()
-            .or(build_api_key_path(profile_path))
-            .unwrap_or(defaults.api_key_path);
+        let (api_key_path, api_key_path_origin) = layered(
+            [
+                (env.api_key_path.clone(), Origin::Env),
+                (
+                    profile.and_then(|p| p.api_key_path.clone()),
+                    Origin::Profile,
+                ),
+            ],
+            build_profile_file_path(profile_path, DEFAULT_API_KEY_FILENAME)
+                .unwrap_or(defaults.api_key_path),
+        );
+        let outbox_path = build_profile_file_path(profile_path, DEFAULT_OUTBOX_FILENAME)
+            .unwrap_or(defaults.outbox_path);
+        let store_path = build_profile_file_path(profile_path, DEFAULT_STORE_FILENAME)
+            .unwrap_or(defaults.store_path);
+        let sync_state_path = build_profile_file_path(profile_path, DEFAULT_SYNC_STATE_FILENAME)
+            .unwrap_or(defaults.sync_state_path);
+        let import_state_path =
+            build_profile_file_path(profile_path, DEFAULT_IMPORT_STATE_FILENAME)
+                .unwrap_or(defaults.import_state_path);
 
-        let token = std::fs::read_to_string(&api_key_path).ok();
+        let (api_key_command, api_key_command_origin) = layered(
+            [(
+                profile.and_then(|p| p.api_key_command.clone().map(Some)),
+                Origin::Profile,
+            )],
+            defaults.api_key_command,
+        );
+        let (api_key_store_command, api_key_store_command_origin) = layered(
+            [(
+                profile.and_then(|p| p.api_key_store_command.clone().map(Some)),
+                Origin::Profile,
+            )],
+            defaults.api_key_store_command,
+        );
+
+        let (encrypt_token, encrypt_token_origin) = layered(
+            [(profile.and_then(|p| p.encrypt_token), Origin::Profile)],
+            defaults.encrypt_token,
+        );
+        let mut encrypted_token = None;
+        let (token, token_origin) = match (&env.token, &api_key_command) {
+            (Some(token), _) => (Some(token.clone()), Origin::Env),
+            // Only run once a command needs the token, see `unlock_token`
+            (None, Some(_)) => (None, Origin::Profile),
+            (None, None) => match auth::load_token(Path::new(&api_key_path)) {
+                Ok(Some(TokenFile::Plain(stored))) => (Some(stored.access_token), Origin::Profile),
+                // Only decrypted once a command needs it, see `unlock_token`
+                Ok(Some(TokenFile::Encrypted(encrypted))) => {
+                    encrypted_token = Some(encrypted);
+                    (None, Origin::Profile)
+                }
+                Ok(None) => (None, Origin::Default),
+                // Like a failing command, a broken file is replaced by logging in again
+                Err(e) => {
+                    eprintln!("Warning: {:#}", e);
+                    (None, Origin::Default)
+                }
+            },
+        };
+
+        let (auth_flow, auth_flow_origin) = layered(
+            [(profile.and_then(|p| p.auth_flow), Origin::Profile)],
+            defaults.auth_flow,
+        );
+        let (oauth_client_id, oauth_client_id_origin) = layered(
+            [(
+                profile.and_then(|p| p.oauth_client_id.clone().map(Some)),
+                Origin::Profile,
+            )],
+            defaults.oauth_client_id,
+        );
+        // The endpoints default to the jot server, which can act as its own identity provider
+        let (oauth_device_url, oauth_device_url_origin) = layered(
+            [(
+                profile.and_then(|p| p.oauth_device_url.clone()),
+                Origin::Profile,
+            )],
+            format!("{}{}", server_url, DEFAULT_OAUTH_DEVICE_PATH),
+        );
+        let (oauth_token_url, oauth_token_url_origin) = layered(
+            [(
+                profile.and_then(|p| p.oauth_token_url.clone()),
+                Origin::Profile,
+            )],
+            format!("{}{}", server_url, DEFAULT_OAUTH_TOKEN_PATH),
+        );
+        let (oauth_scope, oauth_scope_origin) = layered(
+            [(
+                profile.and_then(|p| p.oauth_scope.clone().map(Some)),
+                Origin::Profile,
+            )],
+            defaults.oauth_scope,
+        );
+
+        let (default_tags, default_tags_origin) = layered(
+            [(
+                project.and_then(|p| p.default_tags.clone()),
+                Origin::Project,
+            )],
+            defaults.default_tags,
+        );
+        let (default_output, default_output_origin) = layered(
+            [
+                (env.output, Origin::Env),
+                (project.and_then(|p| p.default_output), Origin::Project),
+            ],
+            defaults.default_output,
+        );
+        let (default_limit, default_limit_origin) = layered(
+            [
+                (env.limit.map(Some), Origin::Env),
+                (
+                    project.and_then(|p| p.default_limit.map(Some)),
+                    Origin::Project,
+                ),
+            ],
+            defaults.default_limit,
+        );
+        let project_date = project
+            .and_then(|p| p.default_date.as_deref())
+            .map(|d| d.parse::<DateValue>().map(Some))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid default_date in .jot.toml: {}", e))?;
+        let (default_date, default_date_origin) =
+            layered([(project_date, Origin::Project)], defaults.default_date);
+
+        let mut layers: Vec<String> = vec![];
+        if profile.is_some() {
+            layers.push(profile_path.to_string_lossy().into_owned());
+        }
+        layers.extend(
+            project
+                .iter()
+                .flat_map(|p| p.files.iter())
+                .map(|f| f.to_string_lossy().into_owned()),
+        );
+
+        let origins = vec![
+            ("backend", backend_origin),
+            ("server_url", server_url_origin),
+            ("timezone", timezone_origin),
+            (
+                "profile_path",
+                args.origins
+                    .get(match args.explicit_profile_path() {
+                        Some(_) => "profile_path",
+                        None => "profile",
+                    })
+                    .copied()
+                    .unwrap_or(Origin::Default),
+            ),
+            ("api_key_path", api_key_path_origin),
+            ("api_key_command", api_key_command_origin),
+            ("api_key_store_command", api_key_store_command_origin),
+            ("token", token_origin),
+            ("encrypt_token", encrypt_token_origin),
+            ("auth_flow", auth_flow_origin),
+            ("oauth_client_id", oauth_client_id_origin),
+            ("oauth_device_url", oauth_device_url_origin),
+            ("oauth_token_url", oauth_token_url_origin),
+            ("oauth_scope", oauth_scope_origin),
+            ("default_tags", default_tags_origin),
+            ("default_output", default_output_origin),
+            ("default_limit", default_limit_origin),
+            ("default_date", default_date_origin),
+        ];
 
         let config = AppConfig {
             #[cfg(debug_assertions)]
@@ -57,15 +429,91 @@ impl AppConfig {
                 .to_str()
                 .map(|p| p.to_string())
                 .unwrap_or(defaults.profile_path),
-            server_url: args
-                .server_url
-                .or(profile_server_url.cloned())
-                .unwrap_or(defaults.server_url),
+            layers,
+            backend,
+            server_url,
+            timezone,
             api_key_path,
+            api_key_command,
+            api_key_store_command,
+            outbox_path,
+            store_path,
+            sync_state_path,
+            import_state_path,
+            default_tags,
+            default_output,
+            default_limit,
+            default_date,
             token,
+            encrypt_token,
+            token_encrypted: encrypted_token.is_some(),
+            encrypted_token,
+            passphrase: env.passphrase.clone(),
+            auth_flow,
+            oauth_client_id,
+            oauth_device_url,
+            oauth_token_url,
+            oauth_scope,
+            origins,
+        };
+
+        Ok(config)
+    }
+
+    /// Runs `api_key_command`, or decrypts the api key file asking for the passphrase unless
+    /// `JOT_PASSPHRASE` is set. Does nothing when the token is known already.
+    pub fn unlock_token(&mut self) -> anyhow::Result<()> {
+        if self.token.is_some() {
+            return Ok(());
+        }
+
+        if let Some(command) = &self.api_key_command {
+            let token = auth::run_key_command(command, None).context("api_key_command failed")?;
+            if token.is_empty() {
+                anyhow::bail!("api_key_command printed no token");
+            }
+
+            self.token = Some(token);
+            return Ok(());
+        }
+
+        let Some(encrypted) = &self.encrypted_token else {
+            return Ok(());
         };
 
-        config
+        let passphrase = auth::read_passphrase(self.passphrase.as_deref(), false)?;
+        let stored = encrypted
+            .decrypt(&passphrase)
+            .context("Couldn't decrypt the token")?;
+
+        self.token = Some(stored.access_token);
+        self.passphrase = Some(passphrase);
+        self.encrypted_token = None;
+
+        Ok(())
+    }
+
+    /// Config of another profile, keeping the mock settings of this run. Environment variables
+    /// and project files only apply to the profile in use, not to this one, except for
+    /// `JOT_PASSPHRASE` so that scripts can unlock its api key file.
+    pub fn for_profile(
+        &self,
+        profile_path: &Path,
+        profile: Option<&Profile>,
+    ) -> anyhow::Result<Self> {
+        let args = ConfigArgs {
+            #[cfg(debug_assertions)]
+            mock: self.mock_server,
+            #[cfg(debug_assertions)]
+            mock_param: self.mock_param.clone(),
+            ..Default::default()
+        };
+        let env = EnvConfig {
+            passphrase: self.passphrase.clone(),
+            ..Default::default()
+        };
+
+        Self::from_args(args, &env, profile_path, profile, None)
     }
 
     #[allow(dead_code)]
@@ -81,11 +529,49 @@ impl AppConfig {
     }
 }
 
+/// Picks the first value that is set, in order of precedence, and where it came from
+fn layered<T, const N: usize>(layers: [(Option<T>, Origin); N], default: T) -> (T, Origin) {
+    layers
+        .into_iter()
+        .find_map(|(value, origin)| value.map(|v| (v, origin)))
+        .unwrap_or((default, Origin::Default))
+}
+
+// Dates are shown the way they are written on the command line
+fn serialize_date_value<S: serde::Serializer>(
+    date: &Option<DateValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    date.as_ref().map(|d| d.to_string()).serialize(serializer)
+}
 
+/// Files belonging to a profile are stored next to it. Apart from the default profile, their
+/// names are prefixed with the profile name so that profiles in one directory don't share them.
+pub fn build_profile_file_path(profile_path: &Path, filename: &str) -> Option<String> {
+    let filename = match profile_path.file_stem().and_then(|s| s.to_str()) {
+        Some(stem) if stem != DEFAULT_PROFILE_NAME => format!("{}.{}", stem, filename),
+        _ => filename.to_string(),
+    };
 
-fn build_api_key_path(profile_path: &Path) -> Option<String> {
     profile_path
         .parent()
-        .map(|p| p.join(Path::new(DEFAULT_API_KEY_FILENAME)))
+        .map(|p| p.join(Path::new(&filename)))
         .map(|p| p.to_string_lossy().into_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_file_path() {
+        assert_eq!(
+            build_profile_file_path(Path::new("/jot/default.toml"), "api_key").unwrap(),
+            "/jot/api_key"
+        );
+        assert_eq!(
+            build_profile_file_path(Path::new("/jot/work.toml"), "api_key").unwrap(),
+            "/jot/work.api_key"
+        );
+    }
+}
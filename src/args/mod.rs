@@ -1,7 +1,14 @@
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::{
+    parser::ValueSource, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::utils::date::{date_filter::DateFilter, date_value::DateValue};
+use crate::{
+    app_config::Origin,
+    utils::date::{date_filter::DateFilter, date_value::DateValue},
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -18,7 +25,27 @@ pub struct CliArgs {
     pub config: ConfigArgs,
 }
 
-#[derive(Debug, Args, Serialize)]
+impl CliArgs {
+    /// Parses the command line, remembering where each global option value came from
+    pub fn parse_with_origins() -> Self {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        for id in matches.ids() {
+            let origin = match matches.value_source(id.as_str()) {
+                Some(ValueSource::CommandLine) => Origin::Arg,
+                Some(ValueSource::EnvVariable) => Origin::Env,
+                _ => continue,
+            };
+
+            args.config.origins.insert(id.to_string(), origin);
+        }
+
+        args
+    }
+}
+
+#[derive(Debug, Default, Args, Serialize)]
 pub struct ConfigArgs {
     /// Mock server requests
     #[cfg(debug_assertions)]
@@ -30,28 +57,240 @@ pub struct ConfigArgs {
     #[arg(long)]
     pub mock_param: Option<String>,
 
-    /// Mock server requests
+    /// Path to the profile file, takes precedence over --profile unless taken from JOT_PROFILE
     #[arg(long, short, env = "JOT_PROFILE")]
     pub profile_path: Option<String>,
 
-    /// Mock server requests
+    /// Name of the profile to use instead of the current one
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// URL of the jot server, overrides the profile
     #[arg(long, short)]
     pub server_url: Option<String>,
+
+    /// Whether values above came from an argument or an env var, filled in by `parse_with_origins`
+    #[arg(skip)]
+    #[serde(skip)]
+    pub origins: HashMap<String, Origin>,
+}
+
+impl ConfigArgs {
+    /// Path of the profile file to use, if one was given. `--profile` on the command line wins
+    /// over a path taken from `JOT_PROFILE`.
+    pub fn explicit_profile_path(&self) -> Option<&str> {
+        let from_env = self.origins.get("profile_path") == Some(&Origin::Env);
+
+        self.profile_path
+            .as_deref()
+            .filter(|_| !(from_env && self.profile.is_some()))
+    }
 }
 
 #[derive(Debug, Subcommand, Serialize, PartialEq)]
 pub enum Command {
     /// Authenticates user against server
-    Login,
-    /// Prints out curent configuration
-    Config,
+    Login(LoginArgs),
+    /// Revokes the token and deletes the api key file
+    Logout(LogoutArgs),
+    /// Prints out curent configuration, or reads and changes profile values
+    Config(ConfigCmdArgs),
     /// Initializes a new profile
-    Init,
+    Init(InitArgs),
     /// Notes subcommands
     #[clap(subcommand)]
     Note(NoteCommand),
     /// Creates a new note. Alias for 'note add'.
     Down(NoteAddArgs),
+    /// Sends queued notes, or syncs the local note store with a server
+    Sync(SyncArgs),
+    /// Exports notes to files
+    #[clap(subcommand)]
+    Export(ExportCommand),
+    /// Imports notes from Markdown files with optional front matter
+    Import(ImportArgs),
+    /// Manages named profiles
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+    /// Checks the profile, login, server and editor and suggests fixes
+    Doctor,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct LoginArgs {
+    /// Print the login URL and a QR code instead of opening a browser
+    #[arg(long, default_value_t = false)]
+    pub no_browser: bool,
+    /// Save a token read from stdin instead of logging in through the browser
+    #[arg(long, default_value_t = false, conflicts_with = "no_browser")]
+    pub with_token: bool,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct LogoutArgs {
+    /// Log out of every named profile
+    #[arg(long, default_value_t = false)]
+    pub all_profiles: bool,
+}
+
+#[derive(Debug, Default, Args, Serialize, PartialEq)]
+pub struct InitArgs {
+    /// Server URL to store in the profile, skips its prompt
+    #[arg(long)]
+    pub server_url: Option<String>,
+
+    /// Api key path to store in the profile, skips its prompt
+    #[arg(long)]
+    pub api_key_path: Option<String>,
+
+    /// Don't prompt, use the defaults for values not given as flags
+    #[arg(long, short, default_value_t = false)]
+    pub yes: bool,
+
+    /// Overwrite an existing profile
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ConfigCmdArgs {
+    #[clap(subcommand)]
+    pub command: Option<ConfigCommand>,
+
+    /// Shows where each value comes from: arg, env, profile or default
+    #[arg(long, default_value_t = false)]
+    pub origin: bool,
+
+    /// Prints secrets such as the token instead of masking them
+    #[arg(long, default_value_t = false)]
+    pub show_secrets: bool,
+
+    /// Output format (json, plain, or toml)
+    #[arg(long, value_enum, default_value_t = ConfigOutputFormat::Json)]
+    pub output: ConfigOutputFormat,
+}
+
+#[derive(Debug, Subcommand, Serialize, PartialEq)]
+pub enum ConfigCommand {
+    /// Prints a value stored in the profile
+    Get(ConfigKeyArgs),
+    /// Stores a value in the profile
+    Set(ConfigSetArgs),
+    /// Removes a value from the profile, so the default applies again
+    Unset(ConfigKeyArgs),
+    /// Opens the profile in $VISUAL or $EDITOR
+    Edit,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ConfigKeyArgs {
+    /// Profile key, e.g. server_url
+    pub key: String,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ConfigSetArgs {
+    /// Profile key, e.g. server_url
+    pub key: String,
+    /// New value
+    pub value: String,
+}
+
+#[derive(Debug, Subcommand, Serialize, PartialEq)]
+pub enum ProfileCommand {
+    /// Lists profiles, the current one is marked with '*'
+    List,
+    /// Switches the current profile
+    Use(ProfileNameArgs),
+    /// Creates a new profile
+    Add(ProfileAddArgs),
+    /// Renames a profile together with its api key and data files
+    Rename(ProfileRenameArgs),
+    /// Deletes a profile together with its api key and data files
+    Remove(ProfileRemoveArgs),
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ProfileNameArgs {
+    /// Name of the profile
+    pub name: String,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ProfileAddArgs {
+    /// Name of the profile
+    pub name: String,
+    #[command(flatten)]
+    pub init: InitArgs,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ProfileRenameArgs {
+    /// Current name of the profile
+    pub from: String,
+    /// New name of the profile
+    pub to: String,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ProfileRemoveArgs {
+    /// Name of the profile
+    pub name: String,
+
+    /// Don't ask for confirmation
+    #[arg(long, short, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct ImportArgs {
+    /// Files or directories to import
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Only show what would be imported
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct SyncArgs {
+    #[command(subcommand)]
+    pub command: Option<SyncCommand>,
+
+    /// Name or path of the profile of the server to sync the local note store with
+    #[arg(long, value_name = "PROFILE")]
+    pub remote: Option<String>,
+
+    /// List every note on the server instead of the ones changed since the last sync, which
+    /// also finds the notes deleted there
+    #[arg(long, default_value_t = false, requires = "remote")]
+    pub full: bool,
+}
+
+#[derive(Debug, Subcommand, Serialize, PartialEq)]
+pub enum SyncCommand {
+    /// Lists the notes that changed on both sides during 'jot sync --remote'
+    Conflicts,
+    /// Removes a conflict from the list, optionally restoring the discarded version
+    Resolve(SyncResolveArgs),
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct SyncResolveArgs {
+    /// Number of the conflict as listed by 'jot sync conflicts'
+    pub number: usize,
+
+    /// Put the discarded version back into the local note, the next sync sends it to the server
+    #[arg(long, default_value_t = false)]
+    pub restore: bool,
+}
+
+#[derive(Debug, Subcommand, Serialize, PartialEq)]
+pub enum ExportCommand {
+    /// Writes one Markdown file with front matter per note.
+    Markdown(ExportMarkdownArgs),
 }
 
 #[derive(Debug, Subcommand, Serialize, PartialEq)]
@@ -62,13 +301,19 @@ pub enum NoteCommand {
     Search(NoteSearchArgs),
     /// Get latest note.
     Last(NoteLatestArgs),
+    /// Edits an existing note in external editor.
+    Edit(NoteEditArgs),
+    /// Shows a single note.
+    Show(NoteShowArgs),
+    /// Deletes notes by id.
+    Delete(NoteDeleteArgs),
 }
 
 #[derive(Debug, Args, Serialize, PartialEq)]
 pub struct NoteAddArgs {
-    /// Assign to current day
-    #[arg(long, short, default_value_t = DateValue::Today)]
-    pub date: DateValue,
+    /// Assign to a date, today unless set in .jot.toml
+    #[arg(long, short)]
+    pub date: Option<DateValue>,
     /// Note content
     #[arg(trailing_var_arg = true)]
     pub content: Vec<String>,
@@ -80,22 +325,54 @@ pub struct NoteAddArgs {
     pub tag: Vec<String>,
 }
 
-#[derive(Debug, Clone, ValueEnum, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct NoteEditArgs {
+    /// Id of the note to edit
+    pub id: i64,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct NoteShowArgs {
+    /// Id of the note to show
+    pub id: i64,
+
+    /// Output format (pretty, plain, or json), pretty unless set in .jot.toml
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
+}
+
+#[derive(Debug, Args, Serialize, PartialEq)]
+pub struct NoteDeleteArgs {
+    /// Ids of the notes to delete
+    #[arg(required = true)]
+    pub ids: Vec<i64>,
+
+    /// Delete without asking for confirmation
+    #[arg(long, short, default_value_t = false)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
+    #[default]
     Pretty,
     Plain,
     Json,
 }
 
-impl Default for OutputFormat {
-    fn default() -> Self {
-        Self::Pretty
-    }
+#[derive(Debug, Clone, Copy, Default, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum ConfigOutputFormat {
+    #[default]
+    Json,
+    /// One `key=value` per line
+    Plain,
+    Toml,
 }
 
-#[derive(Debug, Clone, clap::Args, PartialEq, Serialize, Deserialize)]
-#[command(about = "Search and list notes")]
-pub struct NoteSearchArgs {
+/// Filters shared by the commands that look up notes
+#[derive(Debug, Clone, Default, clap::Args, PartialEq, Serialize, Deserialize)]
+pub struct NoteFilterArgs {
     /// Search term to filter notes
     #[arg(default_value = None)]
     pub term: Option<String>,
@@ -116,21 +393,33 @@ pub struct NoteSearchArgs {
     #[arg(long)]
     pub updated: Option<DateFilter>,
 
+    /// Maximum number of notes
+    #[arg(long, short = 'l')]
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, clap::Args, PartialEq, Serialize, Deserialize)]
+#[command(about = "Search and list notes")]
+pub struct NoteSearchArgs {
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub filter: NoteFilterArgs,
+
     /// Number of lines to display for each note (default: full content)
     #[arg(long, value_name = "N")]
     pub lines: Option<usize>,
 
-    /// Maximum number of results to return
-    #[arg(long, short = 'l')]
-    pub limit: Option<i64>,
-
-    /// Output format (pretty, plain, or json)
-    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
-    pub output: OutputFormat,
+    /// Output format (pretty, plain, or json), pretty unless set in .jot.toml
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
 
     // Ask for found notes to be deleted after displaying
     #[arg(long, default_value_t = false)]
     pub delete: bool,
+
+    /// Delete the found notes without asking for confirmation
+    #[arg(long, short, default_value_t = false, requires = "delete")]
+    pub yes: bool,
 }
 
 #[derive(Debug, clap::Args, PartialEq, Serialize, Deserialize)]
@@ -144,23 +433,20 @@ pub struct NoteLatestArgs {
     #[arg(long, value_name = "TAGS", value_delimiter = ',')]
     pub tag: Vec<String>,
 
-    /// Output format (pretty, plain, or json)
-    #[arg(long, value_enum, default_value_t = OutputFormat::Pretty)]
-    pub output: OutputFormat,
-}
-
-impl Default for NoteSearchArgs {
-    fn default() -> Self {
-        Self {
-            term: None,
-            tag: vec![],
-            date: None,
-            lines: None,
-            created: None,
-            updated: None,
-            limit: None,
-            output: OutputFormat::Pretty,
-            delete: false,
-        }
-    }
+    /// Output format (pretty, plain, or json), pretty unless set in .jot.toml
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
+}
+
+#[derive(Debug, clap::Args, PartialEq, Serialize, Deserialize)]
+pub struct ExportMarkdownArgs {
+    /// Directory to write the files to
+    pub dir: PathBuf,
+
+    #[command(flatten)]
+    pub filter: NoteFilterArgs,
+
+    /// Overwrite files that already exist in the directory
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
 }
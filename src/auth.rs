@@ -1,24 +1,144 @@
-use std::{fs, path::PathBuf, time::Instant};
+use std::{
+    fs,
+    io::{IsTerminal, Read, Write},
+    path::Path,
+    process::Stdio,
+};
 
+use anyhow::Context;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use tokio::time::Instant;
+use wait_timeout::ChildExt;
 
 use crate::{
-    model::{Token, TokenPollResponse},
+    app_config::{AppConfig, AuthFlowKind},
+    encryption::EncryptedToken,
+    env::ENV_PASSPHRASE,
+    model::{DeviceTokenResponse, StoredToken, Token, TokenPollResponse},
+    utils::fs::write_atomic,
     web_client::Client,
 };
 
 const POLLING_INTERVAL: Duration = Duration::from_secs(3);
 const MAX_POLLING_DURATION: Duration = Duration::from_secs(180); // 3 minutes
+/// Added to the polling interval on every `slow_down`, as RFC 8628 requires
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+/// Password managers may wait for a passphrase prompt, but not forever
+const KEY_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
 
-pub struct AuthFlow {}
+/// Identity provider used by the RFC 8628 flow
+#[derive(Debug, Clone)]
+pub struct OAuthSettings {
+    pub client_id: String,
+    pub device_authorization_url: String,
+    pub token_url: String,
+    pub scope: Option<String>,
+}
+
+pub struct AuthFlow {
+    /// Set when logging in through an identity provider instead of the jot server
+    oauth: Option<OAuthSettings>,
+    /// Otherwise the login URL is only printed, together with a QR code
+    open_browser: bool,
+}
 
 impl AuthFlow {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            oauth: None,
+            open_browser: !is_headless(),
+        }
+    }
+
+    pub fn without_browser(self) -> Self {
+        Self {
+            open_browser: false,
+            ..self
+        }
+    }
+
+    /// Flow selected by the `auth_flow` setting of the profile
+    pub fn from_config(config: &AppConfig) -> anyhow::Result<Self> {
+        let oauth = match config.auth_flow {
+            AuthFlowKind::Jot => None,
+            AuthFlowKind::Oauth => Some(OAuthSettings {
+                client_id: config.oauth_client_id.clone().context(
+                    "auth_flow 'oauth' needs a client id, set it with 'jot config set oauth_client_id <id>'",
+                )?,
+                device_authorization_url: config.oauth_device_url.clone(),
+                token_url: config.oauth_token_url.clone(),
+                scope: config.oauth_scope.clone(),
+            }),
+        };
+
+        Ok(Self {
+            oauth,
+            ..Self::new()
+        })
+    }
+
+    pub async fn login(&self, client: &mut dyn Client) -> anyhow::Result<Token> {
+        let result = match &self.oauth {
+            Some(oauth) => self.login_with_device_grant(client, oauth).await,
+            None => self.login_with_jot(client).await,
+        };
+        clear_countdown();
+
+        result
     }
 
-    pub async fn login(&self, client: &mut dyn Client) -> anyhow::Result<String> {
+    /// RFC 8628: the identity provider issues the codes and tells how often to poll
+    async fn login_with_device_grant(
+        &self,
+        client: &dyn Client,
+        oauth: &OAuthSettings,
+    ) -> anyhow::Result<Token> {
+        let authorization = client.authorize_device(oauth).await?;
+
+        println!();
+        println!("    Your code: {}", authorization.user_code);
+        println!();
+        let minutes = authorization.expires_in.div_ceil(60);
+        println!(
+            "Enter it at {} to log in, it expires in {} minute{}.",
+            authorization.verification_uri,
+            minutes,
+            if minutes == 1 { "" } else { "s" }
+        );
+
+        let browser_url = authorization
+            .verification_uri_complete
+            .as_deref()
+            .unwrap_or(&authorization.verification_uri);
+        self.open_browser_or_show_qr(browser_url);
+
+        let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+        let mut interval = Duration::from_secs(authorization.interval);
+
+        while Instant::now() < deadline {
+            wait_with_countdown(interval, deadline).await;
+
+            match client
+                .poll_device_token(oauth, &authorization.device_code)
+                .await?
+            {
+                DeviceTokenResponse::Pending => {}
+                DeviceTokenResponse::SlowDown => interval += SLOW_DOWN_INCREMENT,
+                DeviceTokenResponse::Success(token) => return Ok(token),
+                DeviceTokenResponse::Denied => anyhow::bail!("Login was denied"),
+                DeviceTokenResponse::Expired => break,
+                DeviceTokenResponse::Failure(message) => {
+                    anyhow::bail!("Authentication failed: {}", message);
+                }
+            }
+        }
+
+        anyhow::bail!("The code expired before the login was finished, run 'jot login' again")
+    }
+
+    async fn login_with_jot(&self, client: &mut dyn Client) -> anyhow::Result<Token> {
         // Generate a secure random device code
         let device_code = self.generate_device_code();
 
@@ -29,13 +149,18 @@ impl AuthFlow {
 
         // Generate and open the authentication URL
         let auth_url = format!("{}/auth/page/{}", client.get_server_url(), device_code);
-        println!(
-            "Browser window should open, if not, please visit following URL to login: {}",
-            auth_url
-        );
-
-        #[cfg(not(test))]
-        webbrowser::open(&auth_url)?;
+        if self.open_browser {
+            println!(
+                "Browser window should open, if not, please visit following URL to login: {}",
+                auth_url
+            );
+        } else {
+            println!(
+                "Scan the code below or visit following URL to login: {}",
+                auth_url
+            );
+        }
+        self.open_browser_or_show_qr(&auth_url);
 
         // Poll for completion
         let token = self.poll_for_token(client, &device_code).await?;
@@ -68,15 +193,15 @@ impl AuthFlow {
         &self,
         client: &mut dyn Client,
         device_code: &str,
-    ) -> anyhow::Result<String> {
-        let start_time = Instant::now();
+    ) -> anyhow::Result<Token> {
+        let deadline = Instant::now() + MAX_POLLING_DURATION;
 
-        while start_time.elapsed() < MAX_POLLING_DURATION {
+        while Instant::now() < deadline {
             let response = client.poll_for_token(device_code).await?;
 
             match response {
                 TokenPollResponse::Pending => {
-                    tokio::time::sleep(POLLING_INTERVAL).await;
+                    wait_with_countdown(POLLING_INTERVAL, deadline).await;
                 }
                 TokenPollResponse::Success(token) => {
                     return Ok(token);
@@ -90,35 +215,419 @@ impl AuthFlow {
         anyhow::bail!("Authentication timed out")
     }
 
+    fn open_browser_or_show_qr(&self, url: &str) {
+        if self.open_browser {
+            #[cfg(not(test))]
+            if webbrowser::open(url).is_ok() {
+                return;
+            }
+            println!("Could not open a browser, please open the URL above manually.");
+        }
+
+        print_qr_code(url);
+    }
+
+    #[expect(dead_code)]
     async fn check_auth(&self, client: &mut dyn Client) -> anyhow::Result<()> {
         client.ping().await?;
 
         Ok(())
     }
 
-    #[expect(dead_code)]
-    fn save_token(&self, token_path: &PathBuf, token: &str) -> anyhow::Result<()> {
-        // Ensure the directory exists
-        if let Some(parent) = token_path.parent() {
-            fs::create_dir_all(parent)?;
+    /// Writes the token atomically and readable only by its owner.
+    /// The token is encrypted when a passphrase is given.
+    pub fn save_token(
+        &self,
+        token_path: &Path,
+        token: &StoredToken,
+        passphrase: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let token_file = match passphrase {
+            Some(passphrase) => TokenFile::Encrypted(EncryptedToken::encrypt(token, passphrase)?),
+            None => TokenFile::Plain(token.clone()),
+        };
+        let token_json = serde_json::to_string_pretty(&token_file)?;
+
+        write_atomic(token_path, token_json.as_bytes())
+            .with_context(|| format!("Failed to save the token to {:?}", token_path))?;
+
+        Ok(())
+    }
+}
+
+/// SSH sessions and machines without a graphical session have no browser to show
+pub fn is_headless() -> bool {
+    if std::env::var_os("SSH_CONNECTION").is_some() {
+        return true;
+    }
+
+    // macOS and Windows always have a desktop to open the browser on
+    cfg!(all(unix, not(target_os = "macos")))
+        && std::env::var_os("DISPLAY").is_none()
+        && std::env::var_os("WAYLAND_DISPLAY").is_none()
+}
+
+/// Renders the URL with half-height blocks, so it can be scanned with a phone
+fn print_qr_code(url: &str) {
+    match qrcode::QrCode::new(url.as_bytes()) {
+        Ok(code) => {
+            let image = code
+                .render::<qrcode::render::unicode::Dense1x2>()
+                .quiet_zone(true)
+                .build();
+            println!("{}", image);
+        }
+        Err(e) => eprintln!("Couldn't render the URL as a QR code: {}", e),
+    }
+}
+
+/// Sleeps between polls, counting down the time left for the login on a terminal
+async fn wait_with_countdown(duration: Duration, deadline: Instant) {
+    let until = Instant::now() + duration;
+    let interactive = std::io::stdout().is_terminal();
+
+    loop {
+        let now = Instant::now();
+        if now >= until {
+            break;
+        }
+
+        if interactive {
+            let left = deadline.saturating_duration_since(now).as_secs();
+            print!(
+                "\rWaiting for the login, {}:{:02} left ",
+                left / 60,
+                left % 60
+            );
+            let _ = std::io::stdout().flush();
         }
 
-        // Save the token
-        let token_data = Token {
-            access_token: token.to_string(),
+        tokio::time::sleep((until - now).min(Duration::from_secs(1))).await;
+    }
+}
+
+fn clear_countdown() {
+    if std::io::stdout().is_terminal() {
+        print!("\r\x1b[2K");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Runs `api_key_command` or `api_key_store_command`, split shell-style but without a shell.
+/// `input` is written to its stdin, the first line of stdout is returned trimmed, like `pass`
+/// puts the password on the first line and anything else below. Stderr is left on the
+/// terminal so that password prompts show up.
+pub fn run_key_command(command: &str, input: Option<&str>) -> anyhow::Result<String> {
+    let words =
+        shell_words::split(command).with_context(|| format!("Invalid command '{}'", command))?;
+    let (program, args) = words.split_first().context("Command is empty")?;
+
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        })
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", program))?;
+
+    // Read while the command runs, a full pipe would block it until the timeout
+    let reader = child.stdout.take().map(|mut stdout| {
+        std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        })
+    });
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .and_then(|_| stdin.write_all(b"\n"))
+            .with_context(|| format!("Failed to write to '{}'", program))?;
+        // Dropping stdin closes it, so the command sees the end of the input
+    }
+
+    let Some(status) = child.wait_timeout(KEY_COMMAND_TIMEOUT)? else {
+        let _ = child.kill();
+        let _ = child.wait();
+        anyhow::bail!(
+            "'{}' didn't finish within {} s",
+            program,
+            KEY_COMMAND_TIMEOUT.as_secs()
+        );
+    };
+
+    if !status.success() {
+        anyhow::bail!("'{}' failed with {}", program, status);
+    }
+
+    let output = match reader {
+        Some(reader) => reader
+            .join()
+            .map_err(|_| anyhow::anyhow!("Reading the output of '{}' failed", program))?
+            .with_context(|| format!("Failed to read the output of '{}'", program))?,
+        None => String::new(),
+    };
+
+    Ok(output.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Content of the api key file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TokenFile {
+    Encrypted(EncryptedToken),
+    Plain(StoredToken),
+}
+
+/// Reads the api key file, both the JSON formats and a bare token written by older versions.
+/// Returns `None` when the file doesn't exist.
+pub fn load_token(token_path: &Path) -> anyhow::Result<Option<TokenFile>> {
+    let content = match fs::read_to_string(token_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", token_path)),
+    };
+
+    warn_if_readable_by_others(token_path);
+
+    parse_token(&content)
+        .with_context(|| format!("Invalid token file {:?}", token_path))
+        .map(Some)
+}
+
+fn parse_token(content: &str) -> anyhow::Result<TokenFile> {
+    let content = content.trim();
+
+    if content.starts_with('{') {
+        // The untagged enum would only report that no variant matched
+        return serde_json::from_str(content).map_err(|e| match e.is_data() {
+            true => anyhow::anyhow!("expected an \"access_token\" or an encrypted token"),
+            false => anyhow::anyhow!("not valid JSON: {}", e),
+        });
+    }
+
+    Ok(TokenFile::Plain(StoredToken {
+        access_token: content.to_string(),
+        server_url: None,
+        issued_at: None,
+        expires_at: None,
+    }))
+}
+
+/// Passphrase of an encrypted token file, from `JOT_PASSPHRASE` or asked for on a terminal.
+/// A new passphrase is asked for twice.
+pub fn read_passphrase(env_passphrase: Option<&str>, new: bool) -> anyhow::Result<String> {
+    if let Some(passphrase) = env_passphrase {
+        return Ok(passphrase.to_string());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "The token file is encrypted, set {} to its passphrase",
+            ENV_PASSPHRASE
+        );
+    }
+
+    let prompt = if new {
+        "New passphrase for the token"
+    } else {
+        "Passphrase for the token"
+    };
+    let passphrase = cliclack::password(prompt)
+        .mask('▪')
+        .interact()
+        .context("Couldn't read the passphrase")?;
+
+    if new {
+        let repeated = cliclack::password("Repeat the passphrase")
+            .mask('▪')
+            .interact()
+            .context("Couldn't read the passphrase")?;
+        if repeated != passphrase {
+            anyhow::bail!("The passphrases don't match");
+        }
+    }
+
+    Ok(passphrase)
+}
+
+fn warn_if_readable_by_others(token_path: &Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(metadata) = fs::metadata(token_path) {
+            let mode = metadata.permissions().mode() & 0o777;
+            if mode & 0o077 != 0 {
+                eprintln!(
+                    "Warning: {:?} is readable by other users (mode {:o}), run 'chmod 600 {}'",
+                    token_path,
+                    mode,
+                    token_path.display()
+                );
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = token_path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web_client::mock::{
+        MockClient, MOCK_PARAM_POLL_PREFIX, MOCK_POLL_INTERVAL, MOCK_TOKEN,
+    };
+
+    fn device_grant() -> AuthFlow {
+        AuthFlow {
+            oauth: Some(OAuthSettings {
+                client_id: "jot-cli".to_string(),
+                device_authorization_url: "mocked_instance/device".to_string(),
+                token_url: "mocked_instance/token".to_string(),
+                scope: None,
+            }),
+            open_browser: false,
+        }
+    }
+
+    async fn login_with_script(script: &str) -> (anyhow::Result<Token>, Duration) {
+        let mut client = MockClient::new(Some(&format!("{}{}", MOCK_PARAM_POLL_PREFIX, script)));
+        let start = Instant::now();
+
+        let result = device_grant().login(&mut client).await;
+
+        (result, start.elapsed())
+    }
+
+    fn login_error(result: anyhow::Result<Token>) -> String {
+        let Err(error) = result else {
+            panic!("Login should have failed");
         };
-        let token_json = serde_json::to_string_pretty(&token_data)?;
-        fs::write(token_path, token_json)?;
+        error.to_string()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_grant_waits_while_pending() {
+        let (result, _) = login_with_script("pending,pending,success").await;
+
+        assert_eq!(result.unwrap().access_token, MOCK_TOKEN);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_grant_slows_down() {
+        let (result, elapsed) = login_with_script("slow_down,success").await;
+
+        assert_eq!(result.unwrap().access_token, MOCK_TOKEN);
+        // The second poll waits for the increased interval
+        let interval = Duration::from_secs(MOCK_POLL_INTERVAL);
+        assert_eq!(elapsed, interval + interval + SLOW_DOWN_INCREMENT);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_grant_denied() {
+        let (result, _) = login_with_script("pending,access_denied").await;
+
+        assert_eq!(login_error(result), "Login was denied");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_grant_expired() {
+        let (result, elapsed) = login_with_script("expired_token").await;
+
+        assert!(login_error(result).starts_with("The code expired"));
+        assert_eq!(elapsed, Duration::from_secs(MOCK_POLL_INTERVAL));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_device_grant_deadline() {
+        let (result, elapsed) = login_with_script("pending").await;
+
+        assert!(login_error(result).starts_with("The code expired"));
+        assert_eq!(elapsed, Duration::from_secs(60));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_key_command_keeps_first_line() {
+        // More output than fits into a pipe, the command must not block on it
+        let token = run_key_command(
+            "sh -c 'echo \" secret \"; echo login: me; head -c 1000000 /dev/zero'",
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(token, "secret");
+    }
+
+    #[test]
+    fn test_parse_legacy_token() {
+        let token = parse_token("bare-token\n").unwrap();
+
+        let TokenFile::Plain(token) = token else {
+            panic!("Bare token should be read as plain");
+        };
+        assert_eq!(token.access_token, "bare-token");
+        assert_eq!(token.server_url, None);
+    }
+
+    #[test]
+    fn test_parse_unknown_token_object() {
+        let error = parse_token(r#"{"token": "secret"}"#).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "expected an \"access_token\" or an encrypted token"
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_token() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("api_key");
+        let token = StoredToken {
+            access_token: "secret".to_string(),
+            server_url: Some("https://jot.example.com".to_string()),
+            issued_at: Some(chrono::Utc::now()),
+            expires_at: None,
+        };
+
+        AuthFlow::new().save_token(&path, &token, None).unwrap();
+
+        assert_eq!(load_token(&path).unwrap(), Some(TokenFile::Plain(token)));
 
-        // On Unix-like systems, set file permissions to 600
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(token_path)?.permissions();
-            perms.set_mode(0o600);
-            fs::set_permissions(token_path, perms)?;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
         }
+    }
 
-        Ok(())
+    #[test]
+    fn test_save_encrypted_token() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("api_key");
+        let token = StoredToken {
+            access_token: "secret".to_string(),
+            server_url: None,
+            issued_at: None,
+            expires_at: None,
+        };
+
+        AuthFlow::new()
+            .save_token(&path, &token, Some("passphrase"))
+            .unwrap();
+
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret"));
+        match load_token(&path).unwrap() {
+            Some(TokenFile::Encrypted(encrypted)) => {
+                assert_eq!(encrypted.decrypt("passphrase").unwrap(), token)
+            }
+            other => panic!("Expected an encrypted token, got {:?}", other),
+        }
     }
 }
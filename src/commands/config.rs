@@ -1,8 +1,217 @@
-use crate::app_config::AppConfig;
+use std::path::Path;
 
-pub fn config_cmd(config: AppConfig) -> Result<(), anyhow::Error> {
-    let json = serde_json::to_string_pretty(&config)?;
-    println!("{}", json);
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::{
+    app_config::AppConfig,
+    args::{ConfigCmdArgs, ConfigCommand, ConfigOutputFormat},
+    editor::Editor,
+    profile::Profile,
+    utils::fs::write_atomic,
+};
+
+/// Config values hidden unless `--show-secrets` is given
+const SECRET_KEYS: &[&str] = &["token"];
+const SECRET_MASK: &str = "********";
+
+/// Prefix of the lines describing validation errors in the edited profile
+const ERROR_PREFIX: &str = "# error: ";
+
+pub fn config_cmd(mut config: AppConfig, args: ConfigCmdArgs) -> Result<(), anyhow::Error> {
+    // The passphrase is only asked for when the token is going to be shown
+    if args.show_secrets {
+        if let Err(e) = config.unlock_token() {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
+    let mut values = serde_json::to_value(&config)?;
+    if !args.show_secrets {
+        mask_secrets(&mut values);
+    }
+
+    if args.origin {
+        return print_origins(&config, &values);
+    }
+
+    match args.output {
+        ConfigOutputFormat::Json => println!("{}", serde_json::to_string_pretty(&values)?),
+        ConfigOutputFormat::Plain => {
+            if let Value::Object(map) = &values {
+                for (key, value) in map {
+                    println!("{}={}", key, plain_value(value));
+                }
+            }
+        }
+        ConfigOutputFormat::Toml => {
+            // TOML has no null, unset values are left out
+            if let Value::Object(map) = &mut values {
+                map.retain(|_, v| !v.is_null());
+            }
+            print!(
+                "{}",
+                toml::to_string(&values).context("Failed to serialize config")?
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Secrets are replaced in place so that every output format hides them the same way
+fn mask_secrets(values: &mut Value) {
+    for key in SECRET_KEYS {
+        if let Some(value) = values.get_mut(*key).filter(|v| !v.is_null()) {
+            *value = Value::String(SECRET_MASK.to_string());
+        }
+    }
+}
+
+fn plain_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "".to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Reads and changes values stored in the profile file. Works on the file alone, so that a
+/// profile that fails to load can still be fixed.
+pub fn config_profile_cmd(
+    profile_path: &Path,
+    subcommand: ConfigCommand,
+) -> Result<(), anyhow::Error> {
+    match subcommand {
+        ConfigCommand::Get(args) => {
+            let profile = load_profile(profile_path)?;
+
+            match profile.get(&args.key)? {
+                Some(value) => println!("{}", value),
+                None => anyhow::bail!("'{}' is not set in the profile", args.key),
+            }
+        }
+        ConfigCommand::Set(args) => {
+            let mut table = load_table(profile_path)?;
+
+            // The value is converted to its TOML type through a profile holding only that key
+            let mut single = Profile::default();
+            single.set(&args.key, &args.value)?;
+            let value = toml::Table::try_from(&single)
+                .context("Failed to serialize profile")?
+                .remove(&args.key)
+                .with_context(|| format!("'{}' can't be stored in the profile", args.key))?;
+            table.insert(args.key.clone(), value);
+
+            save_table(profile_path, &table)?;
+            println!("{} = {}", args.key, args.value);
+        }
+        ConfigCommand::Unset(args) => {
+            let mut table = load_table(profile_path)?;
+
+            Profile::default().unset(&args.key)?;
+            table.remove(&args.key);
+
+            save_table(profile_path, &table)?;
+            println!("{} unset", args.key);
+        }
+        ConfigCommand::Edit => edit_profile(profile_path)?,
+    }
+
+    Ok(())
+}
+
+fn load_profile(profile_path: &Path) -> anyhow::Result<Profile> {
+    Profile::from_path(profile_path)?
+        .with_context(|| format!("No profile at {:?}, run 'jot init' first", profile_path))
+}
+
+/// The profile as written, so that `set` and `unset` change only the one key
+fn load_table(profile_path: &Path) -> anyhow::Result<toml::Table> {
+    if !profile_path.exists() {
+        anyhow::bail!("No profile at {:?}, run 'jot init' first", profile_path);
+    }
+
+    let content = std::fs::read_to_string(profile_path).context("Failed to read profile")?;
+
+    toml::from_str(&content).context("Invalid TOML")
+}
+
+/// Validates the whole profile before it's written
+fn save_table(profile_path: &Path, table: &toml::Table) -> anyhow::Result<()> {
+    let content = toml::to_string(table).context("Failed to serialize profile")?;
+    Profile::from_toml_str(&content)
+        .with_context(|| format!("Profile {:?} not saved", profile_path))?;
+
+    write_atomic(profile_path, content.as_bytes()).context("Failed to write profile")
+}
+
+fn print_origins(config: &AppConfig, values: &Value) -> anyhow::Result<()> {
+    let width = config
+        .origins
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or(0);
+
+    for (key, origin) in &config.origins {
+        println!(
+            "{:width$} = {} ({})",
+            key,
+            plain_value(&values[key]),
+            origin,
+            width = width
+        );
+    }
 
     Ok(())
 }
+
+/// Opens the profile in the editor until it validates, then saves it as written
+fn edit_profile(profile_path: &Path) -> anyhow::Result<()> {
+    let original = if profile_path.exists() {
+        std::fs::read_to_string(profile_path).context("Failed to read profile")?
+    } else {
+        String::new()
+    };
+
+    let editor = Editor::new(&original);
+    let mut content = editor.open_str(&original)?;
+
+    loop {
+        let error = match Profile::from_toml_str(&content) {
+            Ok(_) => break,
+            Err(e) => e,
+        };
+
+        let error_comment: String = format!("{:#}", error)
+            .lines()
+            .map(|l| format!("{}{}\n", ERROR_PREFIX, l))
+            .collect();
+        let edited =
+            strip_error_comments(&editor.open_str(&format!("{}{}", error_comment, content))?);
+
+        // Saving without a change gives up instead of asking forever
+        if edited == content {
+            anyhow::bail!("Profile not saved: {:#}", error);
+        }
+        content = edited;
+    }
+
+    if content == original {
+        println!("Profile unchanged");
+        return Ok(());
+    }
+
+    write_atomic(profile_path, content.as_bytes()).context("Failed to write profile")?;
+    println!("Profile saved as {:?}", profile_path);
+
+    Ok(())
+}
+
+fn strip_error_comments(content: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .filter(|l| !l.starts_with(ERROR_PREFIX))
+        .collect()
+}
@@ -0,0 +1,319 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::{
+    app_config::{AppConfig, Backend, Origin},
+    args::ConfigArgs,
+    editor::Editor,
+    env::{EnvConfig, ENV_PASSPHRASE, ENV_TOKEN},
+    profile::Profile,
+    project::ProjectConfig,
+    web_client::{self, ClientError},
+};
+
+/// Clock differences above these make token validation flaky
+const CLOCK_SKEW_WARN_SECS: i64 = 30;
+const CLOCK_SKEW_FAIL_SECS: i64 = 300;
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+    Skip,
+}
+
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn ok(&mut self, name: &str, detail: impl AsRef<str>) {
+        self.print(Status::Ok, name, detail.as_ref(), None);
+    }
+
+    fn warn(&mut self, name: &str, detail: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.print(Status::Warn, name, detail.as_ref(), Some(fix.as_ref()));
+    }
+
+    fn fail(&mut self, name: &str, detail: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.failed += 1;
+        self.print(Status::Fail, name, detail.as_ref(), Some(fix.as_ref()));
+    }
+
+    fn skip(&mut self, name: &str, detail: impl AsRef<str>) {
+        self.print(Status::Skip, name, detail.as_ref(), None);
+    }
+
+    fn print(&self, status: Status, name: &str, detail: &str, fix: Option<&str>) {
+        let label = match status {
+            Status::Ok => "[ok]  ",
+            Status::Warn => "[warn]",
+            Status::Fail => "[fail]",
+            Status::Skip => "[skip]",
+        };
+
+        println!("{} {}: {}", label, name, detail);
+        if let Some(fix) = fix {
+            println!("       fix: {}", fix);
+        }
+    }
+}
+
+/// Checks every step jot needs to work and suggests a fix for each problem.
+/// Runs before the profile is loaded, so it also works with a broken profile.
+pub async fn doctor_cmd(args: ConfigArgs, profile_path: &Path) -> Result<(), anyhow::Error> {
+    let mut report = Report::default();
+
+    let profile = check_profile(&mut report, profile_path);
+
+    let project = match std::env::current_dir().map(|dir| ProjectConfig::discover(&dir)) {
+        Ok(Ok(project)) => {
+            if let Some(project) = &project {
+                report.ok("Project config", format!("{:?}", project.files));
+            }
+            project
+        }
+        Ok(Err(e)) => {
+            report.fail(
+                "Project config",
+                format!("{:#}", e),
+                "Fix or remove the .jot.toml file",
+            );
+            None
+        }
+        Err(e) => {
+            report.fail(
+                "Project config",
+                format!("Current directory is not accessible: {}", e),
+                "Run jot from an existing directory",
+            );
+            None
+        }
+    };
+
+    let config = EnvConfig::from_env().and_then(|env| {
+        AppConfig::from_args(args, &env, profile_path, profile.as_ref(), project.as_ref())
+    });
+
+    match config {
+        Ok(config) if config.backend == Backend::Local => {
+            report.ok(
+                "Backend",
+                format!("local, notes are stored in {}", config.store_path),
+            );
+        }
+        Ok(mut config) => {
+            if config.origins.contains(&("token", Origin::Env)) {
+                report.ok("Api key", format!("taken from {}", ENV_TOKEN));
+            } else if let Some(command) = config.api_key_command.clone() {
+                match config.unlock_token() {
+                    Ok(()) => report.ok("Api key", format!("printed by '{}'", command)),
+                    Err(e) => report.fail(
+                        "Api key",
+                        format!("{:#}", e),
+                        "Run the command yourself, or log in with 'jot login'",
+                    ),
+                }
+            } else {
+                check_api_key(&mut report, &mut config);
+            }
+            check_server(&mut report, &config).await;
+        }
+        Err(e) => {
+            report.fail(
+                "Config",
+                format!("{:#}", e),
+                "Fix the value with 'jot config edit'",
+            );
+        }
+    }
+
+    check_editor(&mut report);
+
+    if report.failed > 0 {
+        anyhow::bail!("{} check(s) failed", report.failed);
+    }
+
+    Ok(())
+}
+
+fn check_profile(report: &mut Report, profile_path: &Path) -> Option<Profile> {
+    match Profile::from_path(profile_path) {
+        Ok(Some(profile)) => match profile.validate() {
+            Ok(()) => {
+                report.ok("Profile", format!("{:?}", profile_path));
+                Some(profile)
+            }
+            Err(e) => {
+                report.fail(
+                    "Profile",
+                    format!("{:?} has an invalid value: {:#}", profile_path, e),
+                    "Fix it with 'jot config edit'",
+                );
+                None
+            }
+        },
+        Ok(None) => {
+            report.fail(
+                "Profile",
+                format!("{:?} not found", profile_path),
+                "Create it with 'jot init'",
+            );
+            None
+        }
+        Err(e) => {
+            report.fail(
+                "Profile",
+                format!("{:?} can't be read: {:#}", profile_path, e),
+                "Fix it with 'jot config edit'",
+            );
+            None
+        }
+    }
+}
+
+fn check_api_key(report: &mut Report, config: &mut AppConfig) {
+    let path = PathBuf::from(&config.api_key_path);
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            report.fail(
+                "Api key",
+                format!("{:?} not found", path),
+                "Log in with 'jot login'",
+            );
+            return;
+        }
+    };
+
+    if metadata.len() == 0 {
+        report.fail(
+            "Api key",
+            format!("{:?} is empty", path),
+            "Log in with 'jot login'",
+        );
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            report.warn(
+                "Api key",
+                format!("{:?} is readable by other users (mode {:o})", path, mode),
+                format!("Run 'chmod 600 {}'", path.display()),
+            );
+        }
+    }
+
+    if let Err(e) = config.unlock_token() {
+        report.fail(
+            "Api key",
+            format!("{:?} is encrypted and couldn't be decrypted: {:#}", path, e),
+            format!("Check the passphrase, or set {}", ENV_PASSPHRASE),
+        );
+        return;
+    }
+
+    match (config.token_encrypted, &config.token) {
+        (true, _) => report.ok("Api key", format!("{:?}, encrypted", path)),
+        (false, _) if config.encrypt_token => report.warn(
+            "Api key",
+            format!("{:?} is not encrypted although encrypt_token is set", path),
+            "Log in again with 'jot login' to encrypt it",
+        ),
+        (false, None) => report.fail(
+            "Api key",
+            format!("{:?} couldn't be read", path),
+            "Log in again with 'jot login'",
+        ),
+        (false, _) => report.ok("Api key", format!("{:?}, not encrypted", path)),
+    }
+}
+
+async fn check_server(report: &mut Report, config: &AppConfig) {
+    let client = web_client::get_client(config);
+
+    let health = match client.health().await {
+        Ok(health) => {
+            report.ok(
+                "Server",
+                format!(
+                    "{} answered in {} ms",
+                    config.server_url,
+                    health.round_trip.as_millis()
+                ),
+            );
+            health
+        }
+        Err(e) => {
+            let fix = if ClientError::is_unreachable(&e) {
+                "Check your network, or the URL with 'jot config set server_url <url>'"
+            } else {
+                "Check the server logs"
+            };
+            report.fail("Server", format!("{:#}", e), fix);
+            report.skip("Login", "server is not reachable");
+            report.skip("Clock", "server is not reachable");
+            return;
+        }
+    };
+
+    match client.ping().await {
+        Ok(Some(account)) => report.ok(
+            "Login",
+            format!("token of {} accepted by /health/auth", account.name),
+        ),
+        Ok(None) => report.ok("Login", "token accepted by /health/auth"),
+        Err(e) => report.fail("Login", format!("{:#}", e), "Log in again with 'jot login'"),
+    }
+
+    match health.server_time {
+        Some(server_time) => {
+            let skew = (Utc::now() - server_time).num_seconds();
+            let detail = format!("local clock differs from the server by {} s", skew);
+            let fix = "Synchronize the system clock, e.g. enable NTP";
+
+            if skew.abs() > CLOCK_SKEW_FAIL_SECS {
+                report.fail("Clock", detail, fix);
+            } else if skew.abs() > CLOCK_SKEW_WARN_SECS {
+                report.warn("Clock", detail, fix);
+            } else {
+                report.ok("Clock", detail);
+            }
+        }
+        None => report.skip("Clock", "server didn't send a Date header"),
+    }
+}
+
+fn check_editor(report: &mut Report) {
+    let editor = Editor::command();
+
+    match find_binary(&editor) {
+        Some(path) => report.ok("Editor", format!("{} ({})", editor, path.display())),
+        None => report.fail(
+            "Editor",
+            format!("'{}' not found", editor),
+            "Set VISUAL or EDITOR to an installed editor",
+        ),
+    }
+}
+
+/// Looks the binary up the same way the shell would, in PATH unless it's a path itself
+fn find_binary(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|p| p.is_file())
+    })
+}
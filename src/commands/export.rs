@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+
+use crate::{
+    app_config::AppConfig,
+    args::{ExportCommand, NoteSearchArgs},
+    commands::login::with_relogin,
+    export::{markdown_file_name, to_markdown},
+    web_client::Client,
+};
+
+pub async fn export_cmd(
+    mut client: Box<dyn Client>,
+    config: &mut AppConfig,
+    subcommand: ExportCommand,
+) -> Result<(), anyhow::Error> {
+    match subcommand {
+        ExportCommand::Markdown(args) => {
+            let search = NoteSearchArgs {
+                filter: args.filter,
+                ..Default::default()
+            };
+            let notes = with_relogin(config, &mut client, async |client| {
+                client.search(&search).await
+            })
+            .await?;
+
+            let mut used = HashSet::new();
+            let files: Vec<_> = notes
+                .notes
+                .iter()
+                .map(|note| (args.dir.join(markdown_file_name(note, &mut used)), note))
+                .collect();
+
+            // Checked up front, so a refused export doesn't leave half of the files behind
+            if !args.force {
+                if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+                    anyhow::bail!("{:?} already exists, use --force to overwrite it", path);
+                }
+            }
+
+            std::fs::create_dir_all(&args.dir)
+                .with_context(|| format!("Failed to create directory {:?}", args.dir))?;
+
+            for (path, note) in &files {
+                std::fs::write(path, to_markdown(note)?)
+                    .with_context(|| format!("Failed to write {:?}", path))?;
+            }
+
+            println!("Exported {} note(s) to {:?}", notes.notes.len(), args.dir);
+        }
+    }
+
+    Ok(())
+}
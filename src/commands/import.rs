@@ -0,0 +1,125 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use crate::{
+    app_config::AppConfig,
+    args::ImportArgs,
+    commands::login::with_relogin,
+    import::{collect_files, content_hash, parse_note_file, ImportState},
+    web_client::{Client, ClientError},
+};
+
+pub async fn import_cmd(
+    mut client: Box<dyn Client>,
+    config: &mut AppConfig,
+    args: ImportArgs,
+) -> Result<(), anyhow::Error> {
+    let state_path = PathBuf::from(&config.import_state_path);
+    let mut state = ImportState::load(&state_path)?;
+    let files = collect_files(&args.paths)?;
+    let today = config.timezone.today();
+
+    let mut seen = HashSet::new();
+    let mut imported = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+
+    for file in files {
+        let bytes = match std::fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("Failed to read {:?}: {}", file, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let hash = content_hash(&bytes);
+        if state.hashes.contains(&hash) || !seen.insert(hash.clone()) {
+            println!("Skipping {:?}, already imported", file);
+            skipped += 1;
+            continue;
+        }
+
+        let template = match String::from_utf8(bytes)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse_note_file(&content))
+        {
+            Ok(template) => template,
+            Err(e) => {
+                eprintln!("Failed to parse {:?}: {}", file, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let date = match template.date {
+            None => today,
+            Some(date) => match date.to_date(today) {
+                Some(date) => date,
+                None => {
+                    eprintln!(
+                        "Failed to parse {:?}: date {:?} is not a day",
+                        file,
+                        date.to_string()
+                    );
+                    failed += 1;
+                    continue;
+                }
+            },
+        };
+        let mut tags: Vec<String> = template.tags.into_iter().collect();
+        tags.sort();
+
+        if args.dry_run {
+            println!(
+                "Would import {:?} [{}] {}: {}",
+                file,
+                date,
+                tags.join(","),
+                template.content.lines().next().unwrap_or_default()
+            );
+            imported += 1;
+            continue;
+        }
+
+        let created = with_relogin(config, &mut client, async |client| {
+            client
+                .create_note(template.content.clone(), tags.clone(), date)
+                .await
+        })
+        .await;
+
+        match created {
+            Ok(note) => {
+                println!("Imported {:?} as note #{}", file, note.id.unwrap_or(0));
+                state.hashes.insert(hash);
+                state.save(&state_path)?;
+                imported += 1;
+            }
+            // Not logged in again, the next import skips the files imported so far
+            Err(e) if ClientError::is_unauthorized(&e) => return Err(e),
+            Err(e) => {
+                eprintln!("Failed to import {:?}: {}", file, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} {} note(s), skipped {}, failed {}",
+        if args.dry_run {
+            "Would import"
+        } else {
+            "Imported"
+        },
+        imported,
+        skipped,
+        failed
+    );
+
+    if failed > 0 {
+        anyhow::bail!("{} file(s) could not be imported", failed);
+    }
+
+    Ok(())
+}
@@ -2,30 +2,31 @@ use std::path::Path;
 
 use anyhow::Context;
 
-use crate::{app_config::AppConfig, init::read_profile};
-
-pub fn init_cmd(config: &AppConfig, profile_path: &Path) -> Result<(), anyhow::Error> {
-    if config.profile_exists {
-        println!("Using profile: {:?}", &profile_path);
-        let new_profile = read_profile(config).context("An error during profile initialization")?;
+use crate::{app_config::AppConfig, args::InitArgs, init::read_profile};
+
+pub fn init_cmd(
+    config: &AppConfig,
+    profile_path: &Path,
+    args: InitArgs,
+) -> Result<(), anyhow::Error> {
+    if config.profile_exists && !args.force {
+        anyhow::bail!(
+            "Profile {:?} already exists, use --force to overwrite it or 'jot config set' to change a value",
+            profile_path
+        );
+    }
 
-        new_profile.save(profile_path)?;
-        println!("Profile updated: {:?}", profile_path);
-    } else {
-        println!("Profile will be saved as '{:?}'", &profile_path);
+    println!("Profile will be saved as {:?}", &profile_path);
 
-        if let Ok(parent) = profile_path
-            .parent()
-            .ok_or_else(|| anyhow::anyhow!("Invalid profile path"))
-        {
-            std::fs::create_dir_all(parent)?;
-        }
+    if let Some(parent) = profile_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
 
-        let new_profile = read_profile(config).context("An error during profile initialization")?;
+    let new_profile =
+        read_profile(config, &args).context("An error during profile initialization")?;
 
-        new_profile.save(profile_path)?;
-        println!("Profile saved as {:?}", profile_path);
-    }
+    new_profile.save(profile_path)?;
+    println!("Profile saved as {:?}", profile_path);
 
     Ok(())
 }
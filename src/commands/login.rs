@@ -1,23 +1,184 @@
-use crate::{auth::AuthFlow, web_client::Client};
+use std::{
+    io::{IsTerminal, Read},
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+
+use crate::{
+    app_config::{AppConfig, Origin},
+    auth::{self, AuthFlow},
+    env::ENV_TOKEN,
+    model::{StoredToken, Token},
+    web_client::{self, Client, ClientError},
+};
 
 pub async fn login_cmd(
     mut client: Box<dyn Client>,
+    auth: AuthFlow,
     profile_path: Option<&str>,
-    api_key_path: &str,
+    config: &AppConfig,
 ) -> Result<(), anyhow::Error> {
     if let Some(profile_path) = profile_path {
         println!("Using profile: {:?}", profile_path);
     }
-    let token = AuthFlow::new().login(client.as_mut()).await;
 
-    match token {
-        Ok(token) => {
-            println!("Api Key Path: {}", api_key_path);
-            std::fs::write(api_key_path, token)?;
-            println!("User successfully logged in.");
+    let token = auth.login(client.as_mut()).await.context("Login failed")?;
+
+    save_login(client.as_ref(), token, config)?;
+    println!("User successfully logged in.");
+
+    Ok(())
+}
+
+/// Saves a token created elsewhere, e.g. a personal access token of a CI job. The token is read
+/// from stdin so it doesn't show up in the process list, and only saved once the server accepts it.
+pub async fn login_with_token_cmd(config: &mut AppConfig) -> anyhow::Result<()> {
+    let token = read_token()?;
+
+    config.token = Some(token.clone());
+    let client = web_client::get_client(config);
+    let account = client
+        .ping()
+        .await
+        .context("The token was not accepted, it was not saved")?;
+
+    let stored = StoredToken {
+        access_token: token,
+        server_url: Some(config.server_url.clone()),
+        issued_at: None,
+        expires_at: None,
+    };
+    store_token(config, &stored)?;
+
+    match account {
+        Some(account) => println!(
+            "Token is valid for {} on {}",
+            account.name, config.server_url
+        ),
+        None => println!("Token is valid for {}", config.server_url),
+    }
+
+    Ok(())
+}
+
+fn read_token() -> anyhow::Result<String> {
+    let token = if std::io::stdin().is_terminal() {
+        cliclack::password("Token")
+            .mask('▪')
+            .interact()
+            .context("Couldn't read the token")?
+    } else {
+        let mut token = String::new();
+        std::io::stdin()
+            .read_to_string(&mut token)
+            .context("Failed to read the token from stdin")?;
+        token
+    };
+
+    let token = token.trim();
+    if token.is_empty() {
+        anyhow::bail!("No token given, pipe it to 'jot login --with-token'");
+    }
+
+    Ok(token.to_string())
+}
+
+/// Checks the login before a command talks to the server, so a rejected token is noticed
+/// before anything is typed or sent. An encrypted token is unlocked here.
+/// Returns a client with a token the server accepts, offering to log in again when it doesn't
+pub async fn ensure_login(config: &mut AppConfig) -> anyhow::Result<Box<dyn Client>> {
+    config.unlock_token()?;
+
+    let mut client = web_client::get_client(config);
+    with_relogin(config, &mut client, async |client| client.ping().await).await?;
+
+    Ok(client)
+}
+
+/// Sends a request and, when the server rejects the token, offers to log in again and sends it
+/// once more. Only the request is repeated, editors and confirmations before it are not.
+pub async fn with_relogin<T>(
+    config: &mut AppConfig,
+    client: &mut Box<dyn Client>,
+    mut request: impl AsyncFnMut(&mut dyn Client) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    match request(client.as_mut()).await {
+        Err(e) if ClientError::is_unauthorized(&e) => {
+            relogin(config, e).await?;
+            *client = web_client::get_client(config);
+            request(client.as_mut()).await
+        }
+        result => result,
+    }
+}
+
+/// Offers to log in again after the server rejected the token. Only on an interactive
+/// terminal, otherwise `error` is returned unchanged.
+pub async fn relogin(config: &mut AppConfig, error: anyhow::Error) -> anyhow::Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return Err(error);
+    }
+
+    // A new key file wouldn't be used while the variable is set
+    if config.origins.contains(&("token", Origin::Env)) {
+        return Err(error.context(format!("The token from {} was rejected", ENV_TOKEN)));
+    }
+
+    let relogin = cliclack::confirm(format!("{:#}. Log in again?", error))
+        .initial_value(true)
+        .interact()?;
+    if !relogin {
+        return Err(error);
+    }
+
+    let mut client = web_client::get_client(config);
+    let token = AuthFlow::from_config(config)?
+        .login(client.as_mut())
+        .await?;
+    config.token = Some(save_login(client.as_ref(), token, config)?);
+
+    Ok(())
+}
+
+/// Stores the token received from the server, returns the access token
+fn save_login(client: &dyn Client, token: Token, config: &AppConfig) -> anyhow::Result<String> {
+    let issued_at = Utc::now();
+    let stored = StoredToken {
+        access_token: token.access_token,
+        server_url: Some(client.get_server_url()),
+        issued_at: Some(issued_at),
+        expires_at: token
+            .expires_in
+            .map(|secs| issued_at + Duration::seconds(secs)),
+    };
+    store_token(config, &stored)?;
+
+    Ok(stored.access_token)
+}
+
+/// Hands the token to `api_key_store_command` when there is one, otherwise writes the key file
+fn store_token(config: &AppConfig, stored: &StoredToken) -> anyhow::Result<()> {
+    match &config.api_key_store_command {
+        Some(command) => {
+            auth::run_key_command(command, Some(&stored.access_token))
+                .context("api_key_store_command failed, the token was not saved")?;
+            println!("Token stored with api_key_store_command");
         }
-        Err(e) => {
-            eprintln!("Error: {}", e);
+        None => {
+            println!("Api Key Path: {}", config.api_key_path);
+
+            let passphrase = match (config.encrypt_token, &config.passphrase) {
+                (false, _) => None,
+                (true, Some(passphrase)) => Some(passphrase.clone()),
+                (true, None) => Some(auth::read_passphrase(None, true)?),
+            };
+            AuthFlow::new().save_token(
+                Path::new(&config.api_key_path),
+                stored,
+                passphrase.as_deref(),
+            )?;
         }
     }
 
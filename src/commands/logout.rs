@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use crate::{
+    app_config::{AppConfig, Backend, Origin},
+    args::LogoutArgs,
+    env::ENV_TOKEN,
+    profile::{Profile, ProfileStore},
+    web_client,
+};
+
+pub async fn logout_cmd(config: &mut AppConfig, args: LogoutArgs) -> Result<(), anyhow::Error> {
+    if !args.all_profiles {
+        return logout(config).await;
+    }
+
+    let store = ProfileStore::from_config_dir()
+        .ok_or_else(|| anyhow::anyhow!("Couldn't determine the config directory"))?;
+
+    let mut failed = 0;
+    for name in store.names()? {
+        let path = store.path(&name);
+        print!("{}: ", name);
+
+        let result = match Profile::from_path(&path) {
+            Ok(profile) => match config.for_profile(&path, profile.as_ref()) {
+                Ok(mut profile_config) => logout(&mut profile_config).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Failed to log out of profile '{}': {:#}", name, e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} profile(s) could not be logged out", failed);
+    }
+
+    Ok(())
+}
+
+/// Revokes the token of one profile and deletes its key file
+async fn logout(config: &mut AppConfig) -> anyhow::Result<()> {
+    if config.backend == Backend::Local {
+        println!("Local backend, nothing to log out of.");
+        return Ok(());
+    }
+
+    if config.origins.contains(&("token", Origin::Env)) {
+        println!(
+            "Token is taken from {}, unset the variable to log out.",
+            ENV_TOKEN
+        );
+        return Ok(());
+    }
+
+    if config.token.is_none()
+        && config.encrypted_token.is_none()
+        && config.api_key_command.is_none()
+    {
+        println!("Already logged out of {}.", config.server_url);
+        return Ok(());
+    }
+
+    // Without the passphrase or the command the token can't be revoked, but the file is still
+    // removed
+    if let Err(e) = config.unlock_token() {
+        eprintln!("Couldn't revoke the token on the server: {:#}", e);
+    } else {
+        let client = web_client::get_client(config);
+        // The token is removed locally anyway, a stale copy on the server is better than a
+        // logout that can't be completed while offline or against a failing server
+        if let Err(e) = client.revoke_token().await {
+            eprintln!("Couldn't revoke the token on the server: {:#}", e);
+        }
+    }
+
+    let key_path = Path::new(&config.api_key_path);
+    if key_path.exists() {
+        std::fs::remove_file(key_path)?;
+    }
+
+    println!("Logged out of {}.", config.server_url);
+
+    if let Some(command) = &config.api_key_command {
+        println!(
+            "The token is still kept where '{}' reads it from, remove it there.",
+            command
+        );
+    }
+
+    Ok(())
+}
@@ -1,4 +1,10 @@
 pub mod config;
+pub mod doctor;
+pub mod export;
+pub mod import;
 pub mod init;
 pub mod login;
+pub mod logout;
 pub mod note;
+pub mod profile;
+pub mod sync;
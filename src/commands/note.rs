@@ -1,12 +1,17 @@
-use anyhow::Context;
-use chrono::Utc;
+use std::{io::IsTerminal, path::Path};
 
 use crate::{
-    args::{NoteCommand, NoteSearchArgs, OutputFormat},
-    editor::{Editor, ParseTemplate},
+    app_config::AppConfig,
+    args::{NoteCommand, NoteFilterArgs, NoteSearchArgs, OutputFormat},
+    commands::login::with_relogin,
+    editor::{Editor, EditorTemplate, ParseTemplate},
     formatters::NoteFormatter,
-    web_client::Client,
+    model::Note,
+    outbox::{Outbox, QueuedNote},
+    utils::date::date_value::DateValue,
+    web_client::{Client, ClientError},
 };
+use anyhow::Context;
 
 const TEMPLATE: &str = r#"tags = ["work", "important"]
 #tags = [""]
@@ -15,91 +20,281 @@ const TEMPLATE: &str = r#"tags = ["work", "important"]
 
 pub async fn note_cmd(
     mut client: Box<dyn Client>,
+    config: &mut AppConfig,
     subcommand: NoteCommand,
 ) -> Result<(), anyhow::Error> {
     match subcommand {
         NoteCommand::Add(args) => {
-            let target_date = args.date.to_date(Utc::now().date_naive());
+            let date = args
+                .date
+                .clone()
+                .or(config.default_date.clone())
+                .unwrap_or(DateValue::Today);
+            let target_date = date.to_date(config.timezone.today());
             if let Some(target_date) = target_date {
-                let note = if args.edit {
+                let (content, tags, target_date) = if args.edit {
                     let editor = Editor::new(TEMPLATE);
-                    let mut result = editor.open(&args)?;
-
-                    while let Err(e) = result.parse_template() {
-                        // Add erorr as a comment to the template
-                        let error = format!("# Error: {}", e);
-                        let mut error_comment = "".to_string();
-                        error
-                            .lines()
-                            .for_each(|l| error_comment.push_str(&format!("# {}\n", l)));
-                        result = editor.open_str(&format!("{}\n{}", error_comment, result))?;
-                    }
-
-                    let template = result.parse_template().context("Error parsing template")?;
+                    let template = read_template(&editor, editor.open(&args)?)?;
 
                     let tags = template.tags.iter().map(|t| t.to_string()).collect();
 
                     let changed_date = template
                         .date
-                        .unwrap_or(args.date.clone())
-                        .to_date(Utc::now().date_naive())
+                        .unwrap_or(date.clone())
+                        .to_date(config.timezone.today())
                         .context("Default is empty")?;
 
-                    client
-                        .create_note(template.content, tags, changed_date)
-                        .await?
+                    (template.content, tags, changed_date)
                 } else {
-                    client
-                        .create_note(args.content.join(" "), args.tag, target_date)
-                        .await?
+                    (args.content.join(" "), args.tag, target_date)
                 };
+                let tags = with_default_tags(tags, &config.default_tags);
+
+                let created = with_relogin(config, &mut client, async |client| {
+                    client
+                        .create_note(content.clone(), tags.clone(), target_date)
+                        .await
+                })
+                .await;
+
+                match created {
+                    Ok(note) => {
+                        NoteFormatter::new(OutputFormat::Pretty, config.timezone)
+                            .print_notes(&[note])?;
+                        println!("Note added, {:?} -> {:?}", date, target_date);
+                    }
+                    Err(e) if ClientError::is_unreachable(&e) => {
+                        let mut outbox = Outbox::load(Path::new(&config.outbox_path))?;
+                        outbox.push(QueuedNote::new(content, tags, target_date))?;
 
-                NoteFormatter::new(OutputFormat::Pretty).print_notes(&[note])?;
+                        println!(
+                            "Server is unreachable, note was queued ({} waiting). Run 'jot sync' to send it.",
+                            outbox.notes().len()
+                        );
+                    }
+                    Err(e) => return Err(e),
+                }
             } else {
                 println!("Invalid date");
             }
-            println!("Note added, {:?} -> {:?}", args.date, target_date);
         }
         NoteCommand::Search(args) => {
-            let notes = client.search(&args).await?;
-            let mut formatter = NoteFormatter::new(args.output);
+            let args = NoteSearchArgs {
+                filter: NoteFilterArgs {
+                    limit: args.filter.limit.or(config.default_limit),
+                    ..args.filter
+                },
+                ..args
+            };
+            let notes = with_relogin(config, &mut client, async |client| {
+                client.search(&args).await
+            })
+            .await?;
+            let mut formatter = NoteFormatter::new(
+                args.output.unwrap_or(config.default_output),
+                config.timezone,
+            );
 
             formatter
                 .print_notes(&notes.notes)
                 .map_err(|e| anyhow::anyhow!("Error while formatting notes: {}", e))?;
 
             if args.delete {
-                println!("Do you want to delete these notes? [y/N]");
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input)?;
-
-                if input.trim().to_lowercase() == "y" {
-                    let ids: Vec<i64> = notes
-                        .notes
-                        .into_iter()
-                        .filter_map(|n| -> Option<i64> { n.id })
-                        .collect();
-                    client.delete(&ids).await?;
-                }
+                delete_notes(config, &mut client, &notes.notes, vec![], args.yes).await?;
             }
         }
         NoteCommand::Last(args) => {
             let args = NoteSearchArgs {
-                term: args.term,
-                tag: args.tag,
-                limit: Some(1),
+                filter: NoteFilterArgs {
+                    term: args.term,
+                    tag: args.tag,
+                    limit: Some(1),
+                    ..Default::default()
+                },
                 output: args.output,
                 ..Default::default()
             };
-            let notes = client.search(&args).await?;
+            let notes = with_relogin(config, &mut client, async |client| {
+                client.search(&args).await
+            })
+            .await?;
 
-            let mut formatter = NoteFormatter::new(args.output);
+            let mut formatter = NoteFormatter::new(
+                args.output.unwrap_or(config.default_output),
+                config.timezone,
+            );
 
             formatter
                 .print_notes(&notes.notes)
                 .map_err(|e| anyhow::anyhow!("Error while formatting notes: {}", e))?;
         }
+        NoteCommand::Edit(args) => {
+            let note = with_relogin(config, &mut client, async |client| {
+                client.get_note(args.id).await
+            })
+            .await?;
+
+            let initial = EditorTemplate::from_note(&note).to_template_string()?;
+            let editor = Editor::new(&initial);
+            let today = config.timezone.today();
+            // Without a date line the note keeps its date
+            let (template, target_date) =
+                read_checked_template(&editor, editor.open_str(&initial)?, |template| {
+                    let target_date = match &template.date {
+                        None => note.target_date,
+                        Some(date) => Some(date.to_date(today).with_context(|| {
+                            format!("Date {:?} is not a day", date.to_string())
+                        })?),
+                    };
+
+                    Ok((template, target_date))
+                })?;
+
+            let mut tags: Vec<String> = template.tags.into_iter().collect();
+            tags.sort();
+            let mut old_tags = note.tags.clone();
+            old_tags.sort();
+
+            if template.content == note.content.trim()
+                && tags == old_tags
+                && target_date == note.target_date
+            {
+                println!("No changes, note #{} was not updated", args.id);
+                return Ok(());
+            }
+
+            // Only the update is sent again after logging in, the edited template is kept
+            let note = with_relogin(config, &mut client, async |client| {
+                client
+                    .update_note(args.id, template.content.clone(), tags.clone(), target_date)
+                    .await
+            })
+            .await?;
+
+            NoteFormatter::new(OutputFormat::Pretty, config.timezone).print_notes(&[note])?;
+            println!("Note #{} updated", args.id);
+        }
+        NoteCommand::Show(args) => {
+            let note = with_relogin(config, &mut client, async |client| {
+                client.get_note(args.id).await
+            })
+            .await?;
+
+            NoteFormatter::new(
+                args.output.unwrap_or(config.default_output),
+                config.timezone,
+            )
+            .print_note_detail(&note)
+            .map_err(|e| anyhow::anyhow!("Error while formatting note: {}", e))?;
+        }
+        NoteCommand::Delete(args) => {
+            let mut notes = vec![];
+            let mut failed = vec![];
+
+            for id in args.ids {
+                match with_relogin(config, &mut client, async |client| {
+                    client.get_note(id).await
+                })
+                .await
+                {
+                    Ok(note) => notes.push(note),
+                    Err(e) if ClientError::is_unauthorized(&e) => return Err(e),
+                    Err(e) => failed.push((id, e.to_string())),
+                }
+            }
+
+            if !notes.is_empty() {
+                NoteFormatter::new(OutputFormat::Pretty, config.timezone).print_notes(&notes)?;
+            }
+
+            delete_notes(config, &mut client, &notes, failed, args.yes).await?;
+        }
     };
 
     Ok(())
 }
+
+/// Asks for confirmation and deletes the notes one by one, reporting the result for each id.
+/// Ids in `failed` were already rejected and are only reported.
+async fn delete_notes(
+    config: &mut AppConfig,
+    client: &mut Box<dyn Client>,
+    notes: &[Note],
+    mut failed: Vec<(i64, String)>,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let ids: Vec<i64> = notes.iter().filter_map(|n| n.id).collect();
+
+    if !ids.is_empty() && !yes {
+        if !std::io::stdin().is_terminal() {
+            anyhow::bail!("Refusing to delete notes without confirmation, use --yes");
+        }
+
+        let confirmed = cliclack::confirm(format!("Delete {} note(s)?", ids.len()))
+            .initial_value(false)
+            .interact()
+            .context("Couldn't read confirmation")?;
+
+        if !confirmed {
+            println!("No notes deleted");
+            return Ok(());
+        }
+    }
+
+    for id in ids {
+        // After logging in again only this note is deleted, the ones before it are done
+        match with_relogin(config, client, async |client| client.delete(&[id]).await).await {
+            Ok(()) => println!("Deleted note #{}", id),
+            Err(e) if ClientError::is_unauthorized(&e) => return Err(e),
+            Err(e) => failed.push((id, e.to_string())),
+        }
+    }
+
+    for (id, error) in &failed {
+        eprintln!("Failed to delete note #{}: {}", id, error);
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("{} note(s) could not be deleted", failed.len());
+    }
+
+    Ok(())
+}
+
+/// Tags from `.jot.toml` are added to the ones given for the note
+fn with_default_tags(mut tags: Vec<String>, default_tags: &[String]) -> Vec<String> {
+    for tag in default_tags {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    tags
+}
+
+/// Parses the editor result, reopening the editor with the error prepended until it's valid
+fn read_template(editor: &Editor, result: String) -> anyhow::Result<EditorTemplate> {
+    read_checked_template(editor, result, Ok)
+}
+
+/// Like [`read_template`], but the editor is also reopened when `check` rejects the template
+fn read_checked_template<T>(
+    editor: &Editor,
+    mut result: String,
+    check: impl Fn(EditorTemplate) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    loop {
+        let e = match result.parse_template().and_then(&check) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        // Add erorr as a comment to the template
+        let error = format!("# Error: {}", e);
+        let mut error_comment = "".to_string();
+        error
+            .lines()
+            .for_each(|l| error_comment.push_str(&format!("# {}\n", l)));
+        result = editor.open_str(&format!("{}\n{}", error_comment, result))?;
+    }
+}
@@ -0,0 +1,165 @@
+use std::{io::IsTerminal, path::Path};
+
+use anyhow::Context;
+
+use crate::{
+    app_config::{
+        build_profile_file_path, AppConfig, Backend, DEFAULT_API_KEY_FILENAME, PROFILE_FILENAMES,
+    },
+    args::{ConfigArgs, ProfileCommand},
+    env::EnvConfig,
+    init::read_profile,
+    profile::{validate_profile_name, Profile, ProfileStore, DEFAULT_PROFILE_NAME},
+};
+
+pub fn profile_cmd(store: &ProfileStore, subcommand: ProfileCommand) -> Result<(), anyhow::Error> {
+    match subcommand {
+        ProfileCommand::List => {
+            let names = store.names()?;
+            if names.is_empty() {
+                println!("No profiles found, create one with 'jot profile add <name>'");
+                return Ok(());
+            }
+
+            let current = store.current();
+            for name in names {
+                let marker = if name == current { "*" } else { " " };
+                let target = match Profile::from_path(&store.path(&name)) {
+                    Ok(Some(p)) if p.backend == Some(Backend::Local) => "local".to_string(),
+                    Ok(Some(p)) => p.server_url.unwrap_or_default(),
+                    _ => "invalid profile".to_string(),
+                };
+
+                println!("{} {} ({})", marker, name, target);
+            }
+        }
+        ProfileCommand::Use(args) => {
+            ensure_exists(store, &args.name)?;
+
+            store.set_current(&args.name)?;
+            println!("Switched to profile '{}'", args.name);
+        }
+        ProfileCommand::Add(args) => {
+            validate_profile_name(&args.name)?;
+
+            let path = store.path(&args.name);
+            if path.exists() && !args.init.force {
+                anyhow::bail!(
+                    "Profile '{}' already exists, use --force to overwrite it",
+                    args.name
+                );
+            }
+
+            std::fs::create_dir_all(store.dir()).context("Failed to create profile directory")?;
+
+            let defaults = AppConfig::from_args(
+                ConfigArgs::default(),
+                &EnvConfig::default(),
+                &path,
+                None,
+                None,
+            )?;
+            let profile = read_profile(&defaults, &args.init)
+                .context("An error during profile initialization")?;
+
+            profile.save(&path)?;
+            println!("Profile '{}' saved as {:?}", args.name, path);
+        }
+        ProfileCommand::Rename(args) => {
+            ensure_exists(store, &args.from)?;
+            validate_profile_name(&args.to)?;
+
+            let from_path = store.path(&args.from);
+            let to_path = store.path(&args.to);
+            if to_path.exists() {
+                anyhow::bail!("Profile '{}' already exists", args.to);
+            }
+
+            let moves: Vec<(String, String)> = PROFILE_FILENAMES
+                .iter()
+                .filter_map(|f| {
+                    build_profile_file_path(&from_path, f).zip(build_profile_file_path(&to_path, f))
+                })
+                .filter(|(from, _)| Path::new(from).exists())
+                .collect();
+
+            if let Some((_, to)) = moves.iter().find(|(_, to)| Path::new(to).exists()) {
+                anyhow::bail!("{:?} already exists, refusing to overwrite it", to);
+            }
+
+            let mut profile = Profile::from_path(&from_path)?.unwrap_or_default();
+            let old_key_path = build_profile_file_path(&from_path, DEFAULT_API_KEY_FILENAME);
+            if profile.api_key_path.is_some() && profile.api_key_path == old_key_path {
+                profile.api_key_path = build_profile_file_path(&to_path, DEFAULT_API_KEY_FILENAME);
+            }
+
+            for (from, to) in &moves {
+                std::fs::rename(from, to).with_context(|| format!("Failed to move {:?}", from))?;
+            }
+            profile.save(&to_path)?;
+            std::fs::remove_file(&from_path).context("Failed to remove old profile")?;
+
+            if store.current() == args.from {
+                store.set_current(&args.to)?;
+            }
+
+            println!("Profile '{}' renamed to '{}'", args.from, args.to);
+        }
+        ProfileCommand::Remove(args) => {
+            ensure_exists(store, &args.name)?;
+
+            if !args.yes {
+                if !std::io::stdin().is_terminal() {
+                    anyhow::bail!("Refusing to remove a profile without confirmation, use --yes");
+                }
+
+                let confirmed = cliclack::confirm(format!(
+                    "Remove profile '{}' with its api key and local data?",
+                    args.name
+                ))
+                .initial_value(false)
+                .interact()
+                .context("Couldn't read confirmation")?;
+
+                if !confirmed {
+                    println!("Profile kept");
+                    return Ok(());
+                }
+            }
+
+            let path = store.path(&args.name);
+            for file in PROFILE_FILENAMES
+                .iter()
+                .filter_map(|f| build_profile_file_path(&path, f))
+            {
+                if Path::new(&file).exists() {
+                    std::fs::remove_file(&file)
+                        .with_context(|| format!("Failed to remove {:?}", file))?;
+                }
+            }
+            std::fs::remove_file(&path).context("Failed to remove profile")?;
+
+            if store.current() == args.name {
+                store.set_current(DEFAULT_PROFILE_NAME)?;
+            }
+
+            println!("Profile '{}' removed", args.name);
+        }
+    }
+
+    Ok(())
+}
+
+fn ensure_exists(store: &ProfileStore, name: &str) -> anyhow::Result<()> {
+    validate_profile_name(name)?;
+
+    if !store.exists(name) {
+        anyhow::bail!(
+            "Profile '{}' not found, create it with 'jot profile add {}'",
+            name,
+            name
+        );
+    }
+
+    Ok(())
+}
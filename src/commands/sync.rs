@@ -0,0 +1,261 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    app_config::{AppConfig, Backend},
+    args::{SyncArgs, SyncCommand, SyncResolveArgs},
+    commands::login::{ensure_login, with_relogin},
+    formatters::TIMESTAMP_FORMAT,
+    outbox::Outbox,
+    profile::{validate_profile_name, Profile, ProfileStore},
+    sync::{SyncSide, SyncState, Synchronizer},
+    web_client::{local::LocalClient, Client, ClientError},
+};
+
+pub async fn sync_cmd(config: &mut AppConfig, args: SyncArgs) -> Result<(), anyhow::Error> {
+    match (args.command, args.remote) {
+        (Some(SyncCommand::Conflicts), _) => list_conflicts(config),
+        (Some(SyncCommand::Resolve(args)), _) => resolve_conflict(config, args).await,
+        (None, Some(remote)) => sync_remote(config, &remote, args.full).await,
+        (None, None) => sync_outbox(config).await,
+    }
+}
+
+fn list_conflicts(config: &AppConfig) -> Result<(), anyhow::Error> {
+    let state = SyncState::load(Path::new(&config.sync_state_path))?;
+
+    if state.conflicts.is_empty() {
+        println!("No sync conflicts");
+        return Ok(());
+    }
+
+    for (i, conflict) in state.conflicts.iter().enumerate() {
+        let (kept, discarded) = side_names(conflict.kept);
+
+        println!(
+            "{}: note #{} (remote #{}), kept the {} version on {}",
+            i + 1,
+            conflict.local_id,
+            conflict.remote_id,
+            kept,
+            config
+                .timezone
+                .format(&conflict.detected_at, TIMESTAMP_FORMAT)
+        );
+        println!(
+            "   discarded {} version from {}: {}",
+            discarded,
+            config
+                .timezone
+                .format(&conflict.discarded.updated_at, TIMESTAMP_FORMAT),
+            conflict
+                .discarded
+                .content
+                .lines()
+                .next()
+                .unwrap_or_default()
+        );
+    }
+
+    println!("Resolve them with 'jot sync resolve <number> [--restore]'");
+
+    Ok(())
+}
+
+async fn resolve_conflict(config: &AppConfig, args: SyncResolveArgs) -> Result<(), anyhow::Error> {
+    let state_path = Path::new(&config.sync_state_path);
+    let mut state = SyncState::load(state_path)?;
+
+    let index = args
+        .number
+        .checked_sub(1)
+        .filter(|i| *i < state.conflicts.len())
+        .with_context(|| {
+            format!(
+                "No conflict {}, list them with 'jot sync conflicts'",
+                args.number
+            )
+        })?;
+    let conflict = state.conflicts.remove(index);
+
+    if args.restore {
+        let discarded = conflict.discarded;
+        LocalClient::new(Path::new(&config.store_path), config.timezone)
+            .update_note(
+                conflict.local_id,
+                discarded.content,
+                discarded.tags,
+                discarded.target_date,
+            )
+            .await?;
+
+        println!(
+            "Restored the {} version of note #{}, the next sync sends it to the server",
+            side_names(conflict.kept).1,
+            conflict.local_id
+        );
+    } else {
+        println!(
+            "Kept the {} version of note #{}",
+            side_names(conflict.kept).0,
+            conflict.local_id
+        );
+    }
+
+    state.save(state_path)
+}
+
+/// Names of the kept and the discarded side
+fn side_names(kept: SyncSide) -> (&'static str, &'static str) {
+    match kept {
+        SyncSide::Local => ("local", "remote"),
+        SyncSide::Remote => ("remote", "local"),
+    }
+}
+
+async fn sync_outbox(config: &mut AppConfig) -> Result<(), anyhow::Error> {
+    let mut client = ensure_login(config).await?;
+
+    let mut outbox = Outbox::load(Path::new(&config.outbox_path))?;
+
+    if outbox.notes().is_empty() {
+        println!("No queued notes to sync");
+        return Ok(());
+    }
+
+    let mut remaining = vec![];
+    let mut synced = 0;
+    let mut unreachable = false;
+    let mut rejected = None;
+
+    for queued in outbox.notes() {
+        // Once the server drops out or rejects the token there is no point in trying the rest
+        if unreachable || rejected.is_some() {
+            remaining.push(queued.clone());
+            continue;
+        }
+
+        let result = with_relogin(config, &mut client, async |client| {
+            client
+                .create_note(
+                    queued.content.clone(),
+                    queued.tags.clone(),
+                    queued.target_date,
+                )
+                .await
+        })
+        .await;
+
+        match result {
+            Ok(_) => {
+                synced += 1;
+                println!(
+                    "Synced note queued at {}: {}",
+                    config.timezone.format(&queued.queued_at, TIMESTAMP_FORMAT),
+                    queued.content.lines().next().unwrap_or_default()
+                );
+            }
+            // Not logged in again, the rest stays queued for the next sync
+            Err(e) if ClientError::is_unauthorized(&e) => {
+                remaining.push(queued.clone());
+                rejected = Some(e);
+            }
+            Err(e) if ClientError::is_unreachable(&e) => {
+                eprintln!("Stopped syncing: {}", e);
+                unreachable = true;
+                remaining.push(queued.clone());
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to sync note queued at {}: {}",
+                    config.timezone.format(&queued.queued_at, TIMESTAMP_FORMAT),
+                    e
+                );
+                remaining.push(queued.clone());
+            }
+        }
+    }
+
+    outbox.replace(remaining)?;
+
+    if let Some(e) = rejected {
+        return Err(e);
+    }
+
+    println!(
+        "{} note(s) synced, {} still queued",
+        synced,
+        outbox.notes().len()
+    );
+
+    if !outbox.notes().is_empty() {
+        anyhow::bail!("Some queued notes could not be synced");
+    }
+
+    Ok(())
+}
+
+async fn sync_remote(config: &AppConfig, remote: &str, full: bool) -> Result<(), anyhow::Error> {
+    if config.backend != Backend::Local {
+        anyhow::bail!("Syncing with a remote needs a profile with backend = \"local\"");
+    }
+
+    // The remote is either a profile name or a path to a profile file
+    let named_path = ProfileStore::from_config_dir()
+        .filter(|_| validate_profile_name(remote).is_ok())
+        .map(|store| store.path(remote))
+        .filter(|path| path.exists());
+    let remote_path = named_path.as_deref().unwrap_or(Path::new(remote));
+    let remote_profile = Profile::from_path(remote_path)?
+        .with_context(|| format!("Remote profile {:?} not found", remote_path))?;
+    let mut remote_config = config.for_profile(remote_path, Some(&remote_profile))?;
+
+    if remote_config.backend != Backend::Web {
+        anyhow::bail!("Remote profile {:?} doesn't use a server", remote_path);
+    }
+
+    let mut remote_client = ensure_login(&mut remote_config).await?;
+
+    let mut local = LocalClient::new(Path::new(&config.store_path), config.timezone);
+    let report = Synchronizer::new(
+        &mut local,
+        remote_client.as_mut(),
+        Path::new(&config.sync_state_path),
+    )
+    .sync(full)
+    .await?;
+
+    println!(
+        "Synced with {}: {} pulled, {} pushed, {} deleted on the server, {} deleted locally ({} fetched)",
+        remote_client.get_server_url(),
+        report.pulled,
+        report.pushed,
+        report.deleted,
+        report.deleted_locally,
+        report.fetched
+    );
+
+    for conflict in &report.conflicts {
+        let (kept, discarded) = side_names(conflict.kept);
+
+        println!(
+            "Conflict: note #{} (remote #{}) changed on both sides, kept the {} version. The {} version from {} is listed by 'jot sync conflicts'",
+            conflict.local_id,
+            conflict.remote_id,
+            kept,
+            discarded,
+            config.timezone.format(&conflict.discarded.updated_at, TIMESTAMP_FORMAT),
+        );
+    }
+
+    for failure in &report.failures {
+        eprintln!("Failed to sync {}", failure);
+    }
+
+    if !report.failures.is_empty() {
+        anyhow::bail!("{} note(s) could not be synced", report.failures.len());
+    }
+
+    Ok(())
+}
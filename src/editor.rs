@@ -4,15 +4,20 @@ use std::{
 };
 
 use anyhow::Context;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{args::NoteAddArgs, utils::date::date_value::DateValue};
+use crate::{args::NoteAddArgs, model::Note, utils::date::date_value::DateValue};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct EditorTemplate {
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_tags")]
     pub tags: HashSet<String>,
-    #[serde(default)]
+    #[serde(
+        default,
+        serialize_with = "serialize_date",
+        deserialize_with = "deserialize_date",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub date: Option<DateValue>,
     #[serde(skip)]
     pub content: String,
@@ -28,6 +33,48 @@ impl Default for EditorTemplate {
     }
 }
 
+impl EditorTemplate {
+    pub fn from_note(note: &Note) -> Self {
+        Self {
+            tags: note.tags.iter().cloned().collect(),
+            date: note.target_date.map(DateValue::Date),
+            content: note.content.clone(),
+        }
+    }
+
+    /// Renders the template in the same layout `ParseTemplate` reads back
+    pub fn to_template_string(&self) -> anyhow::Result<String> {
+        let header = toml::to_string(self).context("Failed to serialize template")?;
+
+        Ok(format!("{}+++\n{}", header, self.content))
+    }
+}
+
+// Tags are written sorted so the template is stable between edits
+fn serialize_tags<S: Serializer>(tags: &HashSet<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut tags: Vec<_> = tags.iter().collect();
+    tags.sort();
+    tags.serialize(serializer)
+}
+
+fn serialize_date<S: Serializer>(
+    date: &Option<DateValue>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    date.as_ref().map(|d| d.to_string()).serialize(serializer)
+}
+
+// Dates are parsed the same way as on the command line, so both "today" and "2024-01-01" work
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateValue>, D::Error> {
+    let value: Option<String> = Option::deserialize(deserializer)?;
+
+    value
+        .map(|v| v.parse::<DateValue>().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 pub struct Editor {
     template: String,
 }
@@ -39,12 +86,14 @@ impl Editor {
         }
     }
 
-    fn read_from_file(&self, tempfile: tempfile::NamedTempFile) -> anyhow::Result<String> {
-        // Read VISUAL or EDITOR environment variable
-        let editor = std::env::var("VISUAL")
-            .unwrap_or_else(|_| std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string()));
+    /// Editor that gets launched: VISUAL, then EDITOR, then vi
+    pub fn command() -> String {
+        std::env::var("VISUAL")
+            .unwrap_or_else(|_| std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string()))
+    }
 
-        let mut child = std::process::Command::new(editor)
+    fn read_from_file(&self, tempfile: tempfile::NamedTempFile) -> anyhow::Result<String> {
+        let mut child = std::process::Command::new(Self::command())
             .arg(tempfile.path())
             .spawn()
             .context("Failed to open editor")?;
@@ -106,20 +155,38 @@ pub trait ParseTemplate {
 }
 
 impl ParseTemplate for String {
+    /// The front matter is parsed as TOML, text without a `+++` line is an error so that the
+    /// editor gets reopened
     fn parse_template(&self) -> anyhow::Result<EditorTemplate> {
-        let parts: Vec<_> = self.split("+++").collect();
-
-        let toml_string = parts[0];
-        let mut parsed_toml = toml::from_str::<EditorTemplate>(toml_string)?;
+        let (front_matter, content) = split_front_matter(self)
+            .context("Missing '+++' line between the front matter and the content")?;
 
-        if parts.len() > 1 {
-            parsed_toml.content = parts[1].trim().to_string();
-        }
+        let mut parsed_toml = toml::from_str::<EditorTemplate>(front_matter)?;
+        parsed_toml.content = content.trim().to_string();
 
         Ok(parsed_toml)
     }
 }
 
+/// Finds the front matter, either TOML before a `+++` line as written by the editor and
+/// `jot export`, or enclosed in `+++` lines. Only whole `+++` lines count as separators.
+pub fn split_front_matter(content: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    let mut separators = content.split_inclusive('\n').filter_map(|line| {
+        let start = offset;
+        offset += line.len();
+        (line.trim_end() == "+++").then_some((start, offset))
+    });
+
+    let (start, end) = separators.next()?;
+    if start == 0 {
+        let (closing_start, closing_end) = separators.next()?;
+        return Some((&content[end..closing_start], &content[closing_end..]));
+    }
+
+    Some((&content[..start], &content[end..]))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -169,6 +236,44 @@ Some content"#
         assert_eq!(parsed.content, "Some content");
     }
 
+    #[test]
+    fn test_parse_template_specific_date() {
+        let template = r#"date = "2024-01-01"
++++
+Some content"#
+            .to_string();
+
+        let parsed = template.parse_template().unwrap();
+
+        assert_eq!(
+            parsed.date,
+            Some(DateValue::Date(
+                chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_template_round_trip() {
+        let template = EditorTemplate {
+            tags: HashSet::from(["work".to_string(), "important".to_string()]),
+            date: Some(DateValue::Date(
+                chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            )),
+            content: "First line\nSecond line".to_string(),
+        };
+
+        let parsed = template
+            .to_template_string()
+            .unwrap()
+            .parse_template()
+            .unwrap();
+
+        assert_eq!(parsed.tags, template.tags);
+        assert_eq!(parsed.date, template.date);
+        assert_eq!(parsed.content, template.content);
+    }
+
     #[test]
     fn test_parse_template_no_date() {
         let template = r#"tags = ["work", "important"]
@@ -182,4 +287,32 @@ Some content"#
         assert_eq!(parsed.tags.len(), 2);
         assert_eq!(parsed.content, "Some content");
     }
+
+    #[test]
+    fn test_parse_template_enclosed_front_matter() {
+        let parsed = "+++\ntags = [\"work\"]\n+++\nEnclosed\n"
+            .to_string()
+            .parse_template()
+            .unwrap();
+
+        assert_eq!(parsed.tags.len(), 1);
+        assert_eq!(parsed.content, "Enclosed");
+    }
+
+    #[test]
+    fn test_parse_template_multi_line_front_matter() {
+        let template = "tags = [\n  \"work\",\n  \"important\",\n]\ndate = \"2024-01-01\"\n+++\nSome content\n";
+
+        let parsed = template.to_string().parse_template().unwrap();
+
+        assert_eq!(parsed.tags.len(), 2);
+        assert!(parsed.tags.contains("important"));
+        assert_eq!(parsed.content, "Some content");
+    }
+
+    #[test]
+    fn test_parse_template_without_separator() {
+        assert!("Just some text\n".to_string().parse_template().is_err());
+        assert!("+++\ntags = []\n".to_string().parse_template().is_err());
+    }
 }
@@ -0,0 +1,164 @@
+use anyhow::Context;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::model::StoredToken;
+
+const KDF_ARGON2ID: &str = "argon2id";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+/// Upper bounds for the Argon2 parameters read from a token file, well above what `encrypt`
+/// writes. A tampered file could otherwise make unlocking take gigabytes of memory or hours.
+const MAX_M_COST: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 10;
+const MAX_P_COST: u32 = 8;
+
+/// Api key file content protected by a passphrase. The key is derived with Argon2id and the
+/// token JSON is sealed with XChaCha20-Poly1305, so a wrong passphrase or a modified file is
+/// detected instead of yielding garbage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedToken {
+    pub kdf: String,
+    /// Argon2 memory cost in KiB
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedToken {
+    pub fn encrypt(token: &StoredToken, passphrase: &str) -> anyhow::Result<Self> {
+        let params = Params::default();
+
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill(&mut salt);
+        rng.fill(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, params.clone())?;
+        let plaintext = serde_json::to_vec(token)?;
+        let ciphertext = XChaCha20Poly1305::new(&key.into())
+            .encrypt(XNonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the token"))?;
+
+        Ok(Self {
+            kdf: KDF_ARGON2ID.to_string(),
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> anyhow::Result<StoredToken> {
+        if self.kdf != KDF_ARGON2ID {
+            anyhow::bail!("Unsupported key derivation '{}'", self.kdf);
+        }
+
+        if self.m_cost > MAX_M_COST || self.t_cost > MAX_T_COST || self.p_cost > MAX_P_COST {
+            anyhow::bail!(
+                "Key derivation parameters m_cost={}, t_cost={}, p_cost={} exceed the limits of {}, {} and {}",
+                self.m_cost,
+                self.t_cost,
+                self.p_cost,
+                MAX_M_COST,
+                MAX_T_COST,
+                MAX_P_COST
+            );
+        }
+
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|e| anyhow::anyhow!("Invalid key derivation parameters: {}", e))?;
+        let salt = STANDARD.decode(&self.salt).context("Invalid salt")?;
+        let nonce = STANDARD.decode(&self.nonce).context("Invalid nonce")?;
+        let ciphertext = STANDARD
+            .decode(&self.ciphertext)
+            .context("Invalid ciphertext")?;
+        if nonce.len() != NONCE_LEN {
+            anyhow::bail!("Invalid nonce length {}", nonce.len());
+        }
+
+        let key = derive_key(passphrase, &salt, params)?;
+        let plaintext = XChaCha20Poly1305::new(&key.into())
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Wrong passphrase or damaged token file"))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: Params) -> anyhow::Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive the key: {}", e))?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> StoredToken {
+        StoredToken {
+            access_token: "secret".to_string(),
+            server_url: Some("https://jot.example.com".to_string()),
+            issued_at: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let encrypted = EncryptedToken::encrypt(&token(), "correct horse").unwrap();
+
+        assert!(!encrypted.ciphertext.contains("secret"));
+        assert_eq!(encrypted.decrypt("correct horse").unwrap(), token());
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let encrypted = EncryptedToken::encrypt(&token(), "correct horse").unwrap();
+
+        let error = encrypted.decrypt("battery staple").unwrap_err();
+
+        assert!(error.to_string().contains("Wrong passphrase"));
+    }
+
+    #[test]
+    fn test_excessive_parameters_are_rejected() {
+        let encrypted = EncryptedToken::encrypt(&token(), "correct horse").unwrap();
+
+        for tampered in [
+            EncryptedToken {
+                m_cost: u32::MAX,
+                ..encrypted.clone()
+            },
+            EncryptedToken {
+                t_cost: u32::MAX,
+                ..encrypted.clone()
+            },
+            EncryptedToken {
+                p_cost: 1024,
+                ..encrypted.clone()
+            },
+        ] {
+            let error = tampered.decrypt("correct horse").unwrap_err();
+
+            assert!(error.to_string().contains("exceed the limits"));
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::Context;
+use clap::ValueEnum;
+
+use crate::{app_config::Backend, args::OutputFormat, utils::date::zone::Zone};
+
+pub const ENV_SERVER_URL: &str = "JOT_SERVER_URL";
+pub const ENV_TOKEN: &str = "JOT_TOKEN";
+pub const ENV_API_KEY_PATH: &str = "JOT_API_KEY_PATH";
+pub const ENV_BACKEND: &str = "JOT_BACKEND";
+pub const ENV_TIMEZONE: &str = "JOT_TIMEZONE";
+pub const ENV_OUTPUT: &str = "JOT_OUTPUT";
+pub const ENV_LIMIT: &str = "JOT_LIMIT";
+pub const ENV_PASSPHRASE: &str = "JOT_PASSPHRASE";
+
+/// Settings taken from `JOT_*` environment variables, empty variables count as unset
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EnvConfig {
+    pub server_url: Option<String>,
+    /// Used instead of reading the api key file
+    pub token: Option<String>,
+    pub api_key_path: Option<String>,
+    pub backend: Option<Backend>,
+    pub timezone: Option<Zone>,
+    pub output: Option<OutputFormat>,
+    pub limit: Option<i64>,
+    /// Unlocks an encrypted api key file without asking
+    pub passphrase: Option<String>,
+}
+
+impl EnvConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+
+        Ok(Self {
+            server_url: var(ENV_SERVER_URL),
+            token: var(ENV_TOKEN).map(|t| t.trim().to_string()),
+            api_key_path: var(ENV_API_KEY_PATH),
+            backend: var(ENV_BACKEND)
+                .map(|b| b.parse())
+                .transpose()
+                .with_context(|| format!("Invalid {}", ENV_BACKEND))?,
+            timezone: var(ENV_TIMEZONE)
+                .map(|t| t.parse())
+                .transpose()
+                .with_context(|| format!("Invalid {}", ENV_TIMEZONE))?,
+            output: var(ENV_OUTPUT)
+                .map(|o| OutputFormat::from_str(&o, true))
+                .transpose()
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", ENV_OUTPUT, e))?,
+            limit: var(ENV_LIMIT)
+                .map(|l| l.parse())
+                .transpose()
+                .with_context(|| format!("Invalid {}", ENV_LIMIT))?,
+            passphrase: var(ENV_PASSPHRASE),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn from_map(vars: &[(&str, &str)]) -> anyhow::Result<EnvConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        EnvConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let env = from_map(&[
+            (ENV_SERVER_URL, "https://jot.example.com"),
+            (ENV_TOKEN, "secret\n"),
+            (ENV_BACKEND, "local"),
+            (ENV_OUTPUT, "JSON"),
+            (ENV_LIMIT, "5"),
+            (ENV_API_KEY_PATH, ""),
+        ])
+        .unwrap();
+
+        assert_eq!(env.server_url.as_deref(), Some("https://jot.example.com"));
+        assert_eq!(env.token.as_deref(), Some("secret"));
+        assert_eq!(env.backend, Some(Backend::Local));
+        assert_eq!(env.output, Some(OutputFormat::Json));
+        assert_eq!(env.limit, Some(5));
+        assert_eq!(env.api_key_path, None);
+    }
+
+    #[test]
+    fn test_invalid_values_name_the_variable() {
+        let error = from_map(&[(ENV_LIMIT, "many")]).unwrap_err();
+
+        assert!(error.to_string().contains(ENV_LIMIT));
+        assert!(from_map(&[(ENV_TIMEZONE, "Nowhere")]).is_err());
+        assert!(from_map(&[(ENV_OUTPUT, "yaml")]).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::model::Note;
+
+const MAX_SLUG_LENGTH: usize = 50;
+
+/// Front matter of an exported note, `tags` and `date` are the keys `ParseTemplate` reads back
+#[derive(Debug, Serialize)]
+pub struct NoteFrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&Note> for NoteFrontMatter {
+    fn from(note: &Note) -> Self {
+        Self {
+            id: note.id,
+            tags: note.tags.clone(),
+            date: note.target_date,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        }
+    }
+}
+
+pub fn to_markdown(note: &Note) -> anyhow::Result<String> {
+    let front_matter = toml::to_string(&NoteFrontMatter::from(note))
+        .context("Failed to serialize front matter")?;
+
+    Ok(format!("{}+++\n{}\n", front_matter, note.content))
+}
+
+/// Names exported files as `<date>-<slug of the first line>.md`, adding the id when the name is taken
+pub fn markdown_file_name(note: &Note, used: &mut HashSet<String>) -> String {
+    let date = note
+        .target_date
+        .unwrap_or_else(|| note.created_at.date_naive());
+    let first_line = note
+        .content
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or_default();
+
+    let base = format!("{}-{}", date.format("%Y-%m-%d"), slugify(first_line));
+
+    let mut name = format!("{}.md", base);
+    if used.contains(&name) {
+        name = format!("{}-{}.md", base, note.id.unwrap_or(0));
+    }
+
+    used.insert(name.clone());
+    name
+}
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(MAX_SLUG_LENGTH).collect();
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() {
+        "note".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::editor::ParseTemplate;
+
+    fn note(content: &str) -> Note {
+        Note {
+            id: Some(7),
+            content: content.to_string(),
+            tags: vec!["work".to_string(), "ideas".to_string()],
+            created_at: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z")
+                .unwrap()
+                .into(),
+            updated_at: DateTime::parse_from_rfc3339("2024-01-03T10:00:00Z")
+                .unwrap()
+                .into(),
+            target_date: NaiveDate::from_ymd_opt(2024, 3, 16),
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Meeting notes: Project X!"),
+            "meeting-notes-project-x"
+        );
+        assert_eq!(slugify("  --Hello,   world--  "), "hello-world");
+        assert_eq!(slugify("Příliš žluťoučký kůň"), "příliš-žluťoučký-kůň");
+        assert_eq!(slugify("!!!"), "note");
+        assert_eq!(slugify(""), "note");
+    }
+
+    #[test]
+    fn test_slugify_long_line() {
+        let slug = slugify(&"word ".repeat(30));
+
+        assert!(slug.chars().count() <= MAX_SLUG_LENGTH);
+        assert!(!slug.ends_with('-'));
+    }
+
+    #[test]
+    fn test_file_name() {
+        let mut used = HashSet::new();
+
+        let first = markdown_file_name(&note("\nFirst line\nSecond line"), &mut used);
+        let second = markdown_file_name(&note("First line"), &mut used);
+
+        assert_eq!(first, "2024-03-16-first-line.md");
+        assert_eq!(second, "2024-03-16-first-line-7.md");
+    }
+
+    #[test]
+    fn test_file_name_without_target_date() {
+        let mut note = note("Something");
+        note.target_date = None;
+
+        assert_eq!(
+            markdown_file_name(&note, &mut HashSet::new()),
+            "2024-01-02-something.md"
+        );
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let note = note("Title\n\nBody with +++ inside");
+
+        let markdown = to_markdown(&note).unwrap();
+        let parsed = markdown.parse_template().unwrap();
+
+        assert!(markdown.starts_with("id = 7\n"));
+        assert!(markdown.contains(r#"created_at = "2024-01-02T10:00:00Z""#));
+        assert_eq!(parsed.tags.len(), 2);
+        assert_eq!(parsed.content, note.content);
+        assert_eq!(
+            parsed.date.and_then(|d| d.to_date(Utc::now().date_naive())),
+            note.target_date
+        );
+    }
+}
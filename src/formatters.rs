@@ -1,14 +1,19 @@
-use crate::{args::OutputFormat, model::Note};
+use crate::{args::OutputFormat, model::Note, utils::date::zone::Zone};
 use std::io::{self, Write};
 use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
+/// Format of timestamps shown to the user, always in the profile timezone
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
 pub struct NoteFormatter {
     output: OutputFormat,
+    zone: Zone,
     writer: BufferWriter,
 }
 
 impl NoteFormatter {
-    pub fn new(output: OutputFormat) -> Self {
+    /// Timestamps are displayed in `zone`, JSON output keeps them in UTC
+    pub fn new(output: OutputFormat, zone: Zone) -> Self {
         let color_choice = match output {
             OutputFormat::Plain => ColorChoice::Never,
             OutputFormat::Json => ColorChoice::Never,
@@ -17,6 +22,7 @@ impl NoteFormatter {
 
         Self {
             output,
+            zone,
             writer: BufferWriter::stdout(color_choice),
         }
     }
@@ -38,6 +44,29 @@ impl NoteFormatter {
         Ok(())
     }
 
+    /// Prints a single note including its timestamps
+    pub fn print_note_detail(&mut self, note: &Note) -> io::Result<()> {
+        let mut buffer = self.writer.buffer();
+
+        match self.output {
+            OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(note).map_err(io::Error::other)?;
+                writeln!(buffer, "{}", json)?;
+            }
+            OutputFormat::Pretty => {
+                self.pretty_print_metadata(&mut buffer, note)?;
+                self.pretty_print_timestamps(&mut buffer, note)?;
+                self.print_content(&mut buffer, &note.content)?;
+            }
+            OutputFormat::Plain => {
+                self.print_note(&mut buffer, note, false)?;
+            }
+        }
+
+        self.writer.print(&buffer)?;
+        Ok(())
+    }
+
     fn print_note(
         &mut self,
         buffer: &mut termcolor::Buffer,
@@ -46,6 +75,7 @@ impl NoteFormatter {
     ) -> io::Result<()> {
         if do_pretty_print {
             self.pretty_print_metadata(buffer, note)?;
+            self.pretty_print_timestamps(buffer, note)?;
         } else {
             self.print_metadata(buffer, note)?;
         }
@@ -84,6 +114,29 @@ impl NoteFormatter {
         Ok(())
     }
 
+    fn pretty_print_timestamps(
+        &self,
+        buffer: &mut termcolor::Buffer,
+        note: &Note,
+    ) -> io::Result<()> {
+        buffer.set_color(
+            ColorSpec::new()
+                .set_fg(Some(Color::Cyan))
+                .set_intense(false),
+        )?;
+
+        writeln!(
+            buffer,
+            "\u{1F552} created {}, updated {}",
+            self.zone.format(&note.created_at, TIMESTAMP_FORMAT),
+            self.zone.format(&note.updated_at, TIMESTAMP_FORMAT)
+        )?;
+
+        buffer.reset()?;
+
+        Ok(())
+    }
+
     fn print_metadata(&self, buffer: &mut termcolor::Buffer, note: &Note) -> io::Result<()> {
         let mut metadata = Vec::new();
 
@@ -95,8 +148,8 @@ impl NoteFormatter {
             metadata.push("".to_string());
         }
 
-        metadata.push(format!("{}", note.created_at.format("%Y-%m-%d %H:%M")));
-        metadata.push(format!("{}", note.updated_at.format("%Y-%m-%d %H:%M")));
+        metadata.push(self.zone.format(&note.created_at, TIMESTAMP_FORMAT));
+        metadata.push(self.zone.format(&note.updated_at, TIMESTAMP_FORMAT));
 
         if !note.tags.is_empty() {
             metadata.push(note.tags.join(","));
@@ -116,8 +169,7 @@ impl NoteFormatter {
     }
 
     fn print_json(&mut self, notes: &[Note], buffer: &mut termcolor::Buffer) -> io::Result<()> {
-        let json = serde_json::to_string_pretty(notes)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let json = serde_json::to_string_pretty(notes).map_err(io::Error::other)?;
 
         writeln!(buffer, "{}", json)?;
         Ok(())
@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    editor::{split_front_matter, EditorTemplate, ParseTemplate},
+    utils::fs::write_atomic,
+};
+
+pub const DEFAULT_IMPORT_STATE_FILENAME: &str = "import_state.json";
+
+/// Extensions picked up when walking directories, files passed explicitly are always imported
+const NOTE_EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+
+/// Hashes of the files that were already imported
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportState {
+    pub hashes: BTreeSet<String>,
+}
+
+impl ImportState {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path).context("Failed to read import state")?;
+
+        serde_json::from_str(&content).context("Failed to parse import state")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content =
+            serde_json::to_string_pretty(self).context("Failed to serialize import state")?;
+
+        write_atomic(path, content.as_bytes()).context("Failed to write import state")
+    }
+}
+
+/// Expands directories into the note files they contain, in a stable order
+pub fn collect_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            collect_dir(path, &mut files)?;
+        } else if path.exists() {
+            files.push(path.clone());
+        } else {
+            anyhow::bail!("{:?} doesn't exist", path);
+        }
+    }
+
+    Ok(files)
+}
+
+fn collect_dir(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory {:?}", dir))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_dir(&entry, files)?;
+        } else if entry
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| NOTE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Parses the front matter the same way as the editor does, files without it are plain content
+pub fn parse_note_file(content: &str) -> anyhow::Result<EditorTemplate> {
+    let template = if has_front_matter(content) {
+        content.to_string().parse_template()?
+    } else {
+        EditorTemplate {
+            tags: Default::default(),
+            date: None,
+            content: content.trim().to_string(),
+        }
+    };
+
+    if template.content.is_empty() {
+        anyhow::bail!("Note has no content");
+    }
+
+    Ok(template)
+}
+
+/// Front matter starts the file with a `+++` line or a `key = value` line, Markdown doesn't
+fn has_front_matter(content: &str) -> bool {
+    let first_line = content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'));
+
+    let starts_like_front_matter = first_line.is_some_and(|line| {
+        line == "+++"
+            || line.split_once('=').is_some_and(|(key, _)| {
+                let key = key.trim();
+                !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            })
+    });
+
+    starts_like_front_matter && split_front_matter(content).is_some()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::date::date_value::DateValue;
+
+    #[test]
+    fn test_parse_note_file_with_front_matter() {
+        let parsed = parse_note_file(
+            r#"id = 3
+tags = ["work"]
+date = "2024-03-16"
+created_at = "2024-01-02T10:00:00Z"
++++
+Imported content
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.tags.len(), 1);
+        assert_eq!(
+            parsed.date,
+            Some(DateValue::Date(
+                chrono::NaiveDate::from_ymd_opt(2024, 3, 16).unwrap()
+            ))
+        );
+        assert_eq!(parsed.content, "Imported content");
+    }
+
+    #[test]
+    fn test_parse_note_file_plain() {
+        let parsed = parse_note_file("# Heading\n\nJust markdown\n").unwrap();
+
+        assert!(parsed.tags.is_empty());
+        assert_eq!(parsed.date, None);
+        assert_eq!(parsed.content, "# Heading\n\nJust markdown");
+    }
+
+    #[test]
+    fn test_parse_note_file_multi_line_front_matter() {
+        let parsed =
+            parse_note_file("tags = [\n  \"work\",\n  \"home\",\n]\n+++\nImported content\n")
+                .unwrap();
+
+        assert_eq!(parsed.tags.len(), 2);
+        assert_eq!(parsed.content, "Imported content");
+    }
+
+    #[test]
+    fn test_parse_note_file_with_separator_in_body() {
+        let content = "# Release notes\n\nC+++ is not a language.\n+++\nStill the body\n";
+
+        let parsed = parse_note_file(content).unwrap();
+
+        assert!(parsed.tags.is_empty());
+        assert_eq!(parsed.content, content.trim());
+    }
+
+    #[test]
+    fn test_parse_note_file_unclosed_separator() {
+        let parsed = parse_note_file("+++\nOnly a divider\n").unwrap();
+
+        assert_eq!(parsed.content, "+++\nOnly a divider");
+    }
+
+    #[test]
+    fn test_parse_note_file_empty() {
+        assert!(parse_note_file("tags = [\"work\"]\n+++\n").is_err());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(content_hash(b"abc"), content_hash(b"abd"));
+    }
+}
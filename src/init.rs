@@ -1,12 +1,41 @@
+use std::io::IsTerminal;
+
 use anyhow::{Context, Ok};
 use cliclack::input;
 
-use crate::{app_config::AppConfig, profile::Profile};
+use crate::{
+    app_config::AppConfig,
+    args::InitArgs,
+    profile::{validate_server_url, Profile},
+};
+
+/// Builds a new profile from the flags, prompting for values that weren't given unless `--yes`
+pub fn read_profile(defaults: &AppConfig, args: &InitArgs) -> anyhow::Result<Profile> {
+    let needs_prompt = !args.yes && (args.server_url.is_none() || args.api_key_path.is_none());
+    if needs_prompt && !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Not running in a terminal, can't ask for profile values. \
+             Pass --yes, optionally with --server-url and --api-key-path."
+        );
+    }
+
+    let server_url = match &args.server_url {
+        Some(url) => url.clone(),
+        None if args.yes => defaults.server_url.clone(),
+        None => read_server_url(&defaults.server_url)?,
+    };
+    validate_server_url(&server_url)?;
+
+    let api_key_path = match &args.api_key_path {
+        Some(path) => path.clone(),
+        None if args.yes => defaults.api_key_path.clone(),
+        None => read_api_key_path(&defaults.api_key_path)?,
+    };
 
-pub fn read_profile(defaults: &AppConfig) -> anyhow::Result<Profile> {
     let profile = Profile {
-        server_url: Some(read_server_url(&defaults.server_url)?),
-        api_key_path: Some(read_api_key_path(&defaults.api_key_path)?),
+        server_url: Some(server_url),
+        api_key_path: Some(api_key_path),
+        ..Default::default()
     };
 
     Ok(profile)
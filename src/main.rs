@@ -1,66 +1,200 @@
 #![deny(clippy::expect_used, clippy::unwrap_used, clippy::panic)]
 #![warn(clippy::expect_used)]
 
-use crate::app_config::AppConfig;
+use crate::app_config::{AppConfig, Backend};
 use anyhow::Context;
-use args::{CliArgs, Command};
-use clap::Parser;
-use commands::{config::config_cmd, init::init_cmd, login::login_cmd, note::note_cmd};
-use profile::{get_profile_path, Profile};
+use args::{CliArgs, Command, ConfigCmdArgs};
+use auth::AuthFlow;
+use commands::{
+    config::{config_cmd, config_profile_cmd},
+    doctor::doctor_cmd,
+    export::export_cmd,
+    import::import_cmd,
+    init::init_cmd,
+    login::{ensure_login, login_cmd, login_with_token_cmd},
+    logout::logout_cmd,
+    note::note_cmd,
+    profile::profile_cmd,
+    sync::sync_cmd,
+};
+use env::EnvConfig;
+use profile::{get_profile_path, Profile, ProfileStore};
+use project::ProjectConfig;
+use std::path::Path;
+use web_client::ClientError;
 
 mod app_config;
 mod args;
 mod auth;
 mod commands;
 mod editor;
+mod encryption;
+mod env;
+mod export;
 mod formatters;
+mod import;
 mod init;
 mod model;
+mod outbox;
 mod profile;
+mod project;
+mod sync;
 mod utils;
 mod web_client;
 
 #[cfg(test)]
 mod test;
 
+/// Exit code of runs that need a new login, `EX_NOPERM` from sysexits.h
+const EXIT_UNAUTHORIZED: i32 = 77;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = CliArgs::parse();
+    let args = CliArgs::parse_with_origins();
 
-    let profile_path = get_profile_path(&args.config.profile_path);
+    let profile_path = get_profile_path(&args.config)?;
 
     if let Some(command) = args.command {
+        // These work even when the profile itself doesn't load
+        let command = match command {
+            Command::Config(ConfigCmdArgs {
+                command: Some(subcommand),
+                ..
+            }) => return Ok(config_profile_cmd(&profile_path, subcommand)?),
+            Command::Doctor => return Ok(doctor_cmd(args.config, &profile_path).await?),
+            command => command,
+        };
+
         let profile = Profile::from_path(&profile_path)?;
-        let config = AppConfig::from_args(args.config, &profile_path, profile.as_ref());
 
-        // Set profile_path variable to Some with the value of profile_path if it exists, otherwise set it to None
-        let profile_path_cond = if profile_path.exists() {
-            Some(profile_path.to_str().context("Unreadable file path.")?)
-        } else {
-            None
+        // A profile picked by name has to exist, unless it's about to be created
+        if let Some(name) = &args.config.profile {
+            if profile.is_none() && !matches!(command, Command::Init(_) | Command::Profile(_)) {
+                return Err(anyhow::anyhow!(
+                    "Profile '{}' not found, create it with 'jot profile add {}'",
+                    name,
+                    name
+                )
+                .into());
+            }
+        }
+
+        // A broken .jot.toml only stops the commands that apply its values
+        let uses_project = !matches!(
+            command,
+            Command::Init(_) | Command::Login(_) | Command::Logout(_) | Command::Profile(_)
+        );
+        let project = match ProjectConfig::discover(&std::env::current_dir()?) {
+            Ok(project) if uses_project => project,
+            Ok(_) => None,
+            Err(e) if uses_project => return Err(e.into()),
+            Err(e) => {
+                eprintln!("Warning: {:#}, it is ignored", e);
+                None
+            }
         };
+        let config = AppConfig::from_args(
+            args.config,
+            &EnvConfig::from_env()?,
+            &profile_path,
+            profile.as_ref(),
+            project.as_ref(),
+        )?;
 
-        match command {
-            Command::Config | Command::Init => match command {
-                Command::Config => config_cmd(config)?,
-                Command::Init => init_cmd(&config, &profile_path)?,
-                _ => unreachable!(),
-            },
-            Command::Login => {
-                let client = web_client::get_client(&config);
-                login_cmd(client, profile_path_cond, &config.api_key_path).await?
+        if let Err(e) = run_command(command, config, &profile_path).await {
+            if ClientError::is_unauthorized(&e) {
+                eprintln!("Error: {:#}", e);
+                eprintln!("Log in again with 'jot login'");
+                std::process::exit(EXIT_UNAUTHORIZED);
             }
-            Command::Note(_) | Command::Down(_) => {
-                let client = web_client::get_client(&config);
 
-                client.ping().await?;
+            return Err(e.into());
+        }
+    }
 
-                match command {
-                    Command::Note(subcommand) => note_cmd(client, subcommand).await?,
-                    Command::Down(args) => note_cmd(client, args::NoteCommand::Add(args)).await?,
-                    _ => unreachable!(),
-                }
+    Ok(())
+}
+
+/// Runs a command once the profile is loaded. When the server rejects the token, the request
+/// that got rejected offers to log in again and is sent once more.
+async fn run_command(
+    command: Command,
+    mut config: AppConfig,
+    profile_path: &Path,
+) -> anyhow::Result<()> {
+    match command {
+        // Never talks to the server
+        Command::Config(args) => config_cmd(config, args),
+        command => dispatch(command, &mut config, profile_path).await,
+    }
+}
+
+async fn dispatch(
+    command: Command,
+    config: &mut AppConfig,
+    profile_path: &Path,
+) -> anyhow::Result<()> {
+    // Set profile_path variable to Some with the value of profile_path if it exists, otherwise set it to None
+    let profile_path_cond = if profile_path.exists() {
+        Some(profile_path.to_str().context("Unreadable file path.")?)
+    } else {
+        None
+    };
+
+    match command {
+        Command::Config(_) => unreachable!("handled before dispatching"),
+        Command::Init(args) => init_cmd(config, profile_path, args)?,
+        Command::Doctor => unreachable!("handled before loading the profile"),
+        Command::Login(_) if config.backend == Backend::Local => {
+            println!("Local backend stores notes on this machine, no login needed.");
+        }
+        Command::Login(args) if args.with_token => login_with_token_cmd(config).await?,
+        Command::Login(args) => {
+            let client = web_client::get_client(config);
+            let mut auth = AuthFlow::from_config(config)?;
+            if args.no_browser {
+                auth = auth.without_browser();
             }
+            login_cmd(client, auth, profile_path_cond, config).await?
+        }
+        Command::Logout(args) => logout_cmd(config, args).await?,
+        Command::Note(_) | Command::Down(_) => {
+            let subcommand = match command {
+                Command::Note(subcommand) => subcommand,
+                Command::Down(args) => args::NoteCommand::Add(args),
+                _ => unreachable!(),
+            };
+
+            let client = match ensure_login(config).await {
+                Ok(client) => client,
+                // New notes are queued while offline and sent later by `jot sync`
+                Err(e)
+                    if matches!(subcommand, args::NoteCommand::Add(_))
+                        && ClientError::is_unreachable(&e) =>
+                {
+                    web_client::get_client(config)
+                }
+                Err(e) => return Err(e),
+            };
+
+            note_cmd(client, config, subcommand).await?
+        }
+        Command::Sync(args) => sync_cmd(config, args).await?,
+        Command::Export(subcommand) => {
+            let client = ensure_login(config).await?;
+
+            export_cmd(client, config, subcommand).await?
+        }
+        Command::Profile(subcommand) => {
+            let store = ProfileStore::from_config_dir()
+                .context("Couldn't determine the config directory")?;
+
+            profile_cmd(&store, subcommand)?
+        }
+        Command::Import(args) => {
+            let client = ensure_login(config).await?;
+
+            import_cmd(client, config, args).await?
         }
     }
 
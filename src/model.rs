@@ -7,10 +7,54 @@ pub struct LoginResponse {
 
 pub enum TokenPollResponse {
     Pending,
-    Success(String),
+    Success(Token),
     #[expect(dead_code)]
     Failure(String),
 }
+
+/// Answer of an RFC 8628 token endpoint while the user logs in
+pub enum DeviceTokenResponse {
+    Pending,
+    Success(Token),
+    Failure(String),
+    /// The identity provider asks to poll less often
+    SlowDown,
+    /// The user refused the login
+    Denied,
+    /// The device code expired before the user finished the login
+    Expired,
+}
+
+/// Device authorization response of an RFC 8628 identity provider
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Verification URI with the user code filled in
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    /// Seconds to wait between polls
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+    /// Seconds until the device code expires
+    pub expires_in: u64,
+}
+
+/// Interval the RFC prescribes when the identity provider doesn't send one
+fn default_poll_interval() -> u64 {
+    5
+}
+
+/// Error response of an OAuth 2.0 token endpoint
+#[derive(Debug, Deserialize)]
+pub struct OAuthError {
+    pub error: String,
+    #[serde(default)]
+    pub error_description: Option<String>,
+}
+
+#[expect(dead_code)]
 #[derive(Serialize, Deserialize)]
 pub struct CreateNoteResponse {
     pub id: i64,
@@ -22,7 +66,7 @@ pub struct GetNotesResponse {
     pub notes: Vec<Note>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
     pub id: Option<i64>,
     pub content: String,
@@ -32,6 +76,7 @@ pub struct Note {
     pub target_date: Option<chrono::NaiveDate>,
 }
 
+#[expect(dead_code)]
 #[derive(Serialize)]
 pub struct PreviewNote {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -53,4 +98,23 @@ pub struct DeviceCodeRequest {
 #[derive(Serialize, Deserialize)]
 pub struct Token {
     pub access_token: String,
+    /// Lifetime of the token in seconds, when the server limits it
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+/// Account a token belongs to, as far as `/health/auth` tells
+#[derive(Debug, Clone, Deserialize)]
+pub struct Account {
+    #[serde(alias = "username", alias = "email")]
+    pub name: String,
+}
+
+/// Content of the api key file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredToken {
+    pub access_token: String,
+    pub server_url: Option<String>,
+    pub issued_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::fs::write_atomic;
+
+pub const DEFAULT_OUTBOX_FILENAME: &str = "outbox.json";
+
+/// Note captured while the server was unreachable, waiting for `jot sync`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedNote {
+    pub content: String,
+    pub tags: Vec<String>,
+    pub target_date: NaiveDate,
+    pub queued_at: DateTime<Utc>,
+}
+
+impl QueuedNote {
+    pub fn new(content: String, tags: Vec<String>, target_date: NaiveDate) -> Self {
+        Self {
+            content,
+            tags,
+            target_date,
+            queued_at: Utc::now(),
+        }
+    }
+}
+
+/// Local queue of notes stored next to the profile, kept in the order they were captured
+pub struct Outbox {
+    path: PathBuf,
+    notes: Vec<QueuedNote>,
+}
+
+impl Outbox {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let notes = if path.exists() {
+            let content = std::fs::read_to_string(path).context("Failed to read outbox")?;
+            serde_json::from_str(&content).context("Failed to parse outbox")?
+        } else {
+            vec![]
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            notes,
+        })
+    }
+
+    pub fn notes(&self) -> &[QueuedNote] {
+        &self.notes
+    }
+
+    pub fn push(&mut self, note: QueuedNote) -> anyhow::Result<()> {
+        self.notes.push(note);
+        self.save()
+    }
+
+    /// Replaces the queue with the notes that are still waiting to be sent
+    pub fn replace(&mut self, notes: Vec<QueuedNote>) -> anyhow::Result<()> {
+        self.notes = notes;
+        self.save()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let content =
+            serde_json::to_string_pretty(&self.notes).context("Failed to serialize outbox")?;
+        write_atomic(&self.path, content.as_bytes()).context("Failed to write outbox")?;
+
+        Ok(())
+    }
+}
@@ -4,14 +4,52 @@ use anyhow::{Context, Ok};
 use config::{Config, File, FileFormat};
 use serde::{Deserialize, Serialize};
 
-use crate::app_config::AppConfig;
+use crate::{
+    app_config::{AppConfig, AuthFlowKind, Backend},
+    args::ConfigArgs,
+    utils::{date::zone::Zone, fs::write_atomic},
+};
 
-const DEFAULT_PROFILE_FILENAME: &str = "default.toml";
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+const PROFILE_EXTENSION: &str = "toml";
+const CURRENT_PROFILE_FILENAME: &str = "current";
+
+/// Keys that can be stored in a profile file
+pub const PROFILE_KEYS: &[&str] = &[
+    "server_url",
+    "api_key_path",
+    "api_key_command",
+    "api_key_store_command",
+    "encrypt_token",
+    "backend",
+    "timezone",
+    "auth_flow",
+    "oauth_client_id",
+    "oauth_device_url",
+    "oauth_token_url",
+    "oauth_scope",
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Profile {
     pub server_url: Option<String>,
     pub api_key_path: Option<String>,
+    /// Prints the token, e.g. `pass show jot`, used instead of the api key file
+    pub api_key_command: Option<String>,
+    /// Receives a new token on stdin after `jot login`, e.g. `pass insert -m jot`
+    pub api_key_store_command: Option<String>,
+    /// Encrypt the api key file with a passphrase
+    pub encrypt_token: Option<bool>,
+    pub backend: Option<Backend>,
+    /// IANA timezone used for relative dates and timestamps, the local one when unset
+    pub timezone: Option<String>,
+    pub auth_flow: Option<AuthFlowKind>,
+    /// Client registered at the identity provider, used by the `oauth` flow
+    pub oauth_client_id: Option<String>,
+    pub oauth_device_url: Option<String>,
+    pub oauth_token_url: Option<String>,
+    /// Space separated scopes requested by the `oauth` flow
+    pub oauth_scope: Option<String>,
 }
 
 impl Default for Profile {
@@ -21,11 +59,146 @@ impl Default for Profile {
         Profile {
             server_url: Some(c.server_url),
             api_key_path: Some(c.api_key_path),
+            api_key_command: None,
+            api_key_store_command: None,
+            encrypt_token: None,
+            backend: None,
+            timezone: None,
+            auth_flow: None,
+            oauth_client_id: None,
+            oauth_device_url: None,
+            oauth_token_url: None,
+            oauth_scope: None,
         }
     }
 }
 
 impl Profile {
+    /// Parses and validates profile TOML, rejecting unknown keys
+    pub fn from_toml_str(content: &str) -> anyhow::Result<Self> {
+        let table: toml::Table = toml::from_str(content).context("Invalid TOML")?;
+        if let Some(key) = table.keys().find(|k| !PROFILE_KEYS.contains(&k.as_str())) {
+            anyhow::bail!(
+                "Unknown key '{}', known keys: {}",
+                key,
+                PROFILE_KEYS.join(", ")
+            );
+        }
+
+        let profile: Self = toml::from_str(content).context("Invalid profile")?;
+        profile.validate()?;
+
+        Ok(profile)
+    }
+
+    /// Checks values that TOML types alone don't cover
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(url) = &self.server_url {
+            validate_server_url(url)?;
+        }
+        if let Some(timezone) = &self.timezone {
+            timezone.parse::<Zone>()?;
+        }
+        for command in [&self.api_key_command, &self.api_key_store_command]
+            .into_iter()
+            .flatten()
+        {
+            validate_command(command)?;
+        }
+        for url in [&self.oauth_device_url, &self.oauth_token_url]
+            .into_iter()
+            .flatten()
+        {
+            validate_server_url(url)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let value = match key {
+            "server_url" => self.server_url.clone(),
+            "api_key_path" => self.api_key_path.clone(),
+            "api_key_command" => self.api_key_command.clone(),
+            "api_key_store_command" => self.api_key_store_command.clone(),
+            "encrypt_token" => self.encrypt_token.map(|e| e.to_string()),
+            "backend" => self.backend.map(|b| b.to_string()),
+            "timezone" => self.timezone.clone(),
+            "auth_flow" => self.auth_flow.map(|f| f.to_string()),
+            "oauth_client_id" => self.oauth_client_id.clone(),
+            "oauth_device_url" => self.oauth_device_url.clone(),
+            "oauth_token_url" => self.oauth_token_url.clone(),
+            "oauth_scope" => self.oauth_scope.clone(),
+            _ => return Err(unknown_key(key)),
+        };
+
+        Ok(value)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "server_url" => {
+                validate_server_url(value)?;
+                self.server_url = Some(value.to_string());
+            }
+            "api_key_path" => self.api_key_path = Some(value.to_string()),
+            "api_key_command" => {
+                validate_command(value)?;
+                self.api_key_command = Some(value.to_string());
+            }
+            "api_key_store_command" => {
+                validate_command(value)?;
+                self.api_key_store_command = Some(value.to_string());
+            }
+            "encrypt_token" => {
+                self.encrypt_token = Some(
+                    value
+                        .parse()
+                        .with_context(|| format!("Invalid value '{}', use true or false", value))?,
+                )
+            }
+            "backend" => self.backend = Some(value.parse()?),
+            "timezone" => {
+                value.parse::<Zone>()?;
+                self.timezone = Some(value.to_string());
+            }
+            "auth_flow" => self.auth_flow = Some(value.parse()?),
+            "oauth_client_id" => self.oauth_client_id = Some(value.to_string()),
+            "oauth_device_url" => {
+                validate_server_url(value)?;
+                self.oauth_device_url = Some(value.to_string());
+            }
+            "oauth_token_url" => {
+                validate_server_url(value)?;
+                self.oauth_token_url = Some(value.to_string());
+            }
+            "oauth_scope" => self.oauth_scope = Some(value.to_string()),
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> anyhow::Result<()> {
+        match key {
+            "server_url" => self.server_url = None,
+            "api_key_path" => self.api_key_path = None,
+            "api_key_command" => self.api_key_command = None,
+            "api_key_store_command" => self.api_key_store_command = None,
+            "encrypt_token" => self.encrypt_token = None,
+            "backend" => self.backend = None,
+            "timezone" => self.timezone = None,
+            "auth_flow" => self.auth_flow = None,
+            "oauth_client_id" => self.oauth_client_id = None,
+            "oauth_device_url" => self.oauth_device_url = None,
+            "oauth_token_url" => self.oauth_token_url = None,
+            "oauth_scope" => self.oauth_scope = None,
+            _ => return Err(unknown_key(key)),
+        }
+
+        Ok(())
+    }
+
     pub fn from_path(profile: &Path) -> anyhow::Result<Option<Self>> {
         if !profile.exists() {
             return Ok(None);
@@ -51,23 +224,202 @@ impl Profile {
     pub fn save(&self, profile_path: &Path) -> anyhow::Result<()> {
         let content = toml::to_string(self).context("Failed to serialize profile")?;
 
-        std::fs::write(profile_path, content).context("Failed to write profile")?;
+        write_atomic(profile_path, content.as_bytes()).context("Failed to write profile")?;
 
         Ok(())
     }
 }
 
-pub fn get_profile_path(arg_profile: &Option<String>) -> PathBuf {
-    let dirs = directories::ProjectDirs::from("com", "beardo", "jot");
+fn unknown_key(key: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Unknown key '{}', known keys: {}",
+        key,
+        PROFILE_KEYS.join(", ")
+    )
+}
+
+pub fn validate_server_url(url: &str) -> anyhow::Result<()> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("Invalid URL '{}'", url))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        anyhow::bail!("URL '{}' has to use http or https", url);
+    }
+
+    Ok(())
+}
+
+/// Commands are split like a shell would, but run without one
+fn validate_command(command: &str) -> anyhow::Result<()> {
+    let words =
+        shell_words::split(command).with_context(|| format!("Invalid command '{}'", command))?;
+    if words.is_empty() {
+        anyhow::bail!("Command can't be empty");
+    }
+
+    Ok(())
+}
+
+/// Resolves the profile to use: an explicit path wins over a profile name, which wins over the
+/// current profile set by `jot profile use`, falling back to the default profile
+pub fn get_profile_path(args: &ConfigArgs) -> anyhow::Result<PathBuf> {
+    if let Some(path) = args.explicit_profile_path() {
+        return Ok(PathBuf::from(path));
+    }
+
+    let Some(store) = ProfileStore::from_config_dir() else {
+        return Ok(Path::new(".").to_path_buf());
+    };
+
+    match &args.profile {
+        Some(name) => {
+            validate_profile_name(name)?;
+            Ok(store.path(name))
+        }
+        None => Ok(store.path(&store.current())),
+    }
+}
+
+/// Named profiles stored side by side as `<name>.toml` in the config directory
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    pub fn from_config_dir() -> Option<Self> {
+        directories::ProjectDirs::from("com", "beardo", "jot").map(|d| Self::new(d.config_dir()))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", name, PROFILE_EXTENSION))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
 
-    let xdg_path = dirs.map(|dir| {
-        let d = dir.config_dir();
-        d.join(DEFAULT_PROFILE_FILENAME).to_path_buf()
-    });
+    /// Names of all stored profiles, sorted
+    pub fn names(&self) -> anyhow::Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut names = vec![];
+        for entry in std::fs::read_dir(&self.dir).context("Failed to read profile directory")? {
+            let path = entry?.path();
 
-    let arg_path = arg_profile.clone().map(PathBuf::from);
+            if path.extension().is_some_and(|e| e == PROFILE_EXTENSION) {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
 
-    arg_path
-        .or(xdg_path)
-        .unwrap_or_else(|| Path::new(".").to_path_buf())
+        Ok(names)
+    }
+
+    /// Profile selected by `jot profile use`, or the default one
+    pub fn current(&self) -> String {
+        std::fs::read_to_string(self.dir.join(CURRENT_PROFILE_FILENAME))
+            .ok()
+            .map(|n| n.trim().to_string())
+            .filter(|n| validate_profile_name(n).is_ok())
+            .unwrap_or_else(|| DEFAULT_PROFILE_NAME.to_string())
+    }
+
+    pub fn set_current(&self, name: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir).context("Failed to create profile directory")?;
+        std::fs::write(self.dir.join(CURRENT_PROFILE_FILENAME), name)
+            .context("Failed to save current profile")
+    }
+}
+
+/// Profile names end up in file names, so only a safe subset of characters is allowed
+pub fn validate_profile_name(name: &str) -> anyhow::Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        anyhow::bail!(
+            "Invalid profile name '{}', use only letters, digits, '-' and '_'",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_profile_name() {
+        assert!(validate_profile_name("work").is_ok());
+        assert!(validate_profile_name("my_server-2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("../etc").is_err());
+        assert!(validate_profile_name("a b").is_err());
+    }
+
+    #[test]
+    fn test_set_validates_values() {
+        let mut profile = Profile::default();
+
+        profile
+            .set("server_url", "https://jot.example.com")
+            .unwrap();
+        profile.set("backend", "local").unwrap();
+        profile.set("timezone", "Europe/Prague").unwrap();
+
+        assert!(profile.set("server_url", "jot.example.com").is_err());
+        assert!(profile.set("backend", "cloud").is_err());
+        assert!(profile.set("timezone", "Mars/Olympus").is_err());
+        assert!(profile.set("colour", "blue").is_err());
+        assert_eq!(
+            profile.get("server_url").unwrap().as_deref(),
+            Some("https://jot.example.com")
+        );
+        assert_eq!(profile.get("backend").unwrap().as_deref(), Some("local"));
+
+        profile.unset("timezone").unwrap();
+        assert_eq!(profile.get("timezone").unwrap(), None);
+    }
+
+    #[test]
+    fn test_from_toml_str() {
+        assert!(Profile::from_toml_str("server_url = \"http://localhost:9000\"").is_ok());
+        assert!(Profile::from_toml_str("server_url = 1").is_err());
+        assert!(Profile::from_toml_str("sever_url = \"http://localhost\"").is_err());
+        assert!(Profile::from_toml_str("timezone = \"Nowhere\"").is_err());
+    }
+
+    #[test]
+    fn test_store_current_and_names() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = ProfileStore::new(dir.path());
+
+        assert_eq!(store.current(), DEFAULT_PROFILE_NAME);
+        assert!(store.names().unwrap().is_empty());
+
+        Profile::default().save(&store.path("work")).unwrap();
+        Profile::default().save(&store.path("home")).unwrap();
+        std::fs::write(dir.path().join("work.api_key"), "token").unwrap();
+        store.set_current("work").unwrap();
+
+        assert_eq!(store.current(), "work");
+        assert_eq!(store.names().unwrap(), vec!["home", "work"]);
+    }
 }
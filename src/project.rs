@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::args::OutputFormat;
+
+pub const PROJECT_CONFIG_FILENAME: &str = ".jot.toml";
+
+/// Settings of a `.jot.toml` found in the current directory or one of its parents.
+/// The server and backend can't be set here, a checked out repository must not be able to
+/// send the profile's token somewhere else.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub timezone: Option<String>,
+    /// Tags added to every new note
+    pub default_tags: Option<Vec<String>>,
+    pub default_output: Option<OutputFormat>,
+    pub default_limit: Option<i64>,
+    /// Date assigned to new notes, same syntax as `--date`
+    pub default_date: Option<String>,
+    /// Files merged into this config, outermost first
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+impl ProjectConfig {
+    /// Merges all `.jot.toml` files from `start` up to the root, the closest one wins.
+    /// Returns `None` when there are none.
+    pub fn discover(start: &Path) -> anyhow::Result<Option<Self>> {
+        let mut files: Vec<PathBuf> = start
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILENAME))
+            .filter(|path| path.is_file())
+            .collect();
+        files.reverse();
+
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let mut merged = Self::default();
+        for file in files {
+            let layer = Self::from_path(&file)?;
+            merged = merged.merge(layer);
+            merged.files.push(file);
+        }
+
+        Ok(Some(merged))
+    }
+
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+
+        toml::from_str(&content).with_context(|| format!("Invalid project config {:?}", path))
+    }
+
+    /// Values set in `other` replace those of `self`
+    fn merge(self, other: Self) -> Self {
+        Self {
+            timezone: other.timezone.or(self.timezone),
+            default_tags: other.default_tags.or(self.default_tags),
+            default_output: other.default_output.or(self.default_output),
+            default_limit: other.default_limit.or(self.default_limit),
+            default_date: other.default_date.or(self.default_date),
+            files: self.files,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_merges_closest_last() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let nested = dir.path().join("project/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            dir.path().join(PROJECT_CONFIG_FILENAME),
+            "default_tags = [\"outer\"]\ndefault_limit = 5\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("project").join(PROJECT_CONFIG_FILENAME),
+            "default_tags = [\"project-x\"]\ndefault_output = \"plain\"\n",
+        )
+        .unwrap();
+
+        let config = ProjectConfig::discover(&nested).unwrap().unwrap();
+
+        assert_eq!(config.default_tags, Some(vec!["project-x".to_string()]));
+        assert_eq!(config.default_limit, Some(5));
+        assert_eq!(config.default_output, Some(OutputFormat::Plain));
+        assert_eq!(
+            config.files,
+            vec![
+                dir.path().join(PROJECT_CONFIG_FILENAME),
+                dir.path().join("project").join(PROJECT_CONFIG_FILENAME)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILENAME);
+        std::fs::write(&path, "default_tag = [\"typo\"]\n").unwrap();
+
+        assert!(ProjectConfig::from_path(&path).is_err());
+    }
+
+    #[test]
+    fn test_server_url_is_rejected() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILENAME);
+        std::fs::write(&path, "server_url = \"https://example.com\"\n").unwrap();
+
+        assert!(ProjectConfig::from_path(&path).is_err());
+    }
+}
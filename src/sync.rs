@@ -0,0 +1,429 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::{NoteFilterArgs, NoteSearchArgs},
+    model::Note,
+    utils::{
+        date::{date_filter::DateFilter, date_value::DateValue},
+        fs::write_atomic,
+    },
+    web_client::{local::LocalClient, Client},
+};
+
+pub const DEFAULT_SYNC_STATE_FILENAME: &str = "sync_state.json";
+
+/// Most notes asked for by the search of an incremental sync. The search can't page, so a
+/// response this long may have left notes out.
+const UPDATED_LIMIT: i64 = 500;
+
+/// Pair of notes kept in sync, with the versions seen at the end of the last sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncLink {
+    pub local_id: i64,
+    pub remote_id: i64,
+    pub local_updated_at: DateTime<Utc>,
+    pub remote_updated_at: DateTime<Utc>,
+}
+
+/// Both sides changed the same note, the older version is kept here for manual resolution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub local_id: i64,
+    pub remote_id: i64,
+    pub kept: SyncSide,
+    pub discarded: Note,
+    pub detected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncSide {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncState {
+    pub remote: Option<String>,
+    pub last_sync: Option<DateTime<Utc>>,
+    pub links: Vec<SyncLink>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl SyncState {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path).context("Failed to read sync state")?;
+
+        serde_json::from_str(&content).context("Failed to parse sync state")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content =
+            serde_json::to_string_pretty(self).context("Failed to serialize sync state")?;
+
+        write_atomic(path, content.as_bytes()).context("Failed to write sync state")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Notes received from the server, only the changed ones unless the sync lists all of them
+    pub fetched: usize,
+    pub pulled: usize,
+    pub pushed: usize,
+    /// Deleted on the server because they were deleted locally
+    pub deleted: usize,
+    /// Deleted locally because they were deleted on the server
+    pub deleted_locally: usize,
+    pub conflicts: Vec<SyncConflict>,
+    pub failures: Vec<String>,
+}
+
+/// Two-way sync between the local note store and a server.
+///
+/// After the first sync only the notes the server updated since the last one are fetched. When
+/// there are too many for one search, all notes are listed instead. The server can't list
+/// deleted notes, so finding those needs a full sync that lists all of them.
+/// Only notes whose `updated_at` differs from the version recorded in the sync state are written
+/// or pushed. When both sides changed a note, the newer version wins and the other one is
+/// recorded as a conflict instead of being dropped.
+pub struct Synchronizer<'a> {
+    local: &'a mut LocalClient,
+    remote: &'a mut dyn Client,
+    state_path: PathBuf,
+    updated_limit: i64,
+}
+
+impl<'a> Synchronizer<'a> {
+    pub fn new(local: &'a mut LocalClient, remote: &'a mut dyn Client, state_path: &Path) -> Self {
+        Self {
+            local,
+            remote,
+            state_path: state_path.to_path_buf(),
+            updated_limit: UPDATED_LIMIT,
+        }
+    }
+
+    /// A `full` sync lists every note on the server, which also finds the ones deleted there
+    pub async fn sync(&mut self, full: bool) -> anyhow::Result<SyncReport> {
+        let started_at = Utc::now();
+        let remote_url = self.remote.get_server_url();
+
+        let mut state = SyncState::load(&self.state_path)?;
+        if state.remote.as_ref().is_some_and(|r| *r != remote_url) {
+            println!(
+                "Sync state belongs to {}, starting a full sync with {}",
+                state.remote.as_deref().unwrap_or_default(),
+                remote_url
+            );
+            state = SyncState::default();
+        }
+
+        let mut report = SyncReport::default();
+
+        let local_notes: HashMap<i64, Note> = self
+            .local
+            .get_notes()
+            .await?
+            .notes
+            .into_iter()
+            .filter_map(|n| n.id.map(|id| (id, n)))
+            .collect();
+
+        let mut changed_since = state.last_sync.filter(|_| !full);
+        let mut remote_notes = match changed_since {
+            Some(last_sync) => {
+                self.remote
+                    .search(&updated_since(last_sync, self.updated_limit))
+                    .await?
+                    .notes
+            }
+            None => self.remote.get_notes().await?.notes,
+        };
+        // A full response may have cut off some of the updated notes, and advancing `last_sync`
+        // past them would skip them for good
+        if changed_since.is_some() && remote_notes.len() as i64 >= self.updated_limit {
+            changed_since = None;
+            remote_notes = self.remote.get_notes().await?.notes;
+        }
+        report.fetched = remote_notes.len();
+
+        let mut links: HashMap<i64, SyncLink> = state
+            .links
+            .iter()
+            .map(|l| (l.remote_id, l.clone()))
+            .collect();
+        let mut handled_local: HashSet<i64> = HashSet::new();
+
+        // Notes deleted on the server are deleted locally as well. One that was edited locally
+        // since the last sync is kept and pushed again as a new note. Only a full listing tells
+        // which notes are gone.
+        let remote_ids: HashSet<i64> = remote_notes.iter().filter_map(|n| n.id).collect();
+        let gone: Vec<SyncLink> = links
+            .values()
+            .filter(|l| changed_since.is_none() && !remote_ids.contains(&l.remote_id))
+            .cloned()
+            .collect();
+
+        for link in gone {
+            let unchanged = local_notes
+                .get(&link.local_id)
+                .is_some_and(|n| n.updated_at == link.local_updated_at);
+
+            if unchanged {
+                handled_local.insert(link.local_id);
+                if let Err(e) = self.local.delete(&[link.local_id]).await {
+                    report
+                        .failures
+                        .push(format!("Note #{}: {}", link.local_id, e));
+                    continue;
+                }
+                report.deleted_locally += 1;
+            }
+
+            links.remove(&link.remote_id);
+        }
+
+        for remote_note in remote_notes {
+            let Some(remote_id) = remote_note.id else {
+                continue;
+            };
+
+            let link = links.get(&remote_id).cloned();
+            if link
+                .as_ref()
+                .is_some_and(|l| l.remote_updated_at == remote_note.updated_at)
+            {
+                continue;
+            }
+
+            let local_note = link
+                .as_ref()
+                .and_then(|l| local_notes.get(&l.local_id))
+                .cloned();
+
+            let local_changed = match (&link, &local_note) {
+                (Some(link), Some(local)) => local.updated_at != link.local_updated_at,
+                _ => false,
+            };
+
+            if let (Some(link), Some(local), true) = (&link, &local_note, local_changed) {
+                handled_local.insert(link.local_id);
+
+                let (kept, discarded) = if local.updated_at > remote_note.updated_at {
+                    (SyncSide::Local, remote_note.clone())
+                } else {
+                    (SyncSide::Remote, local.clone())
+                };
+
+                report.conflicts.push(SyncConflict {
+                    local_id: link.local_id,
+                    remote_id,
+                    kept,
+                    discarded,
+                    detected_at: started_at,
+                });
+
+                let synced = match kept {
+                    SyncSide::Local => self.push_update(remote_id, local).await,
+                    SyncSide::Remote => self
+                        .local
+                        .put_note(Some(link.local_id), &remote_note)
+                        .map(|l| (l, remote_note.clone())),
+                };
+
+                match synced {
+                    Ok((local, remote)) => {
+                        links.insert(remote_id, new_link(&local, &remote)?);
+                    }
+                    Err(e) => report.failures.push(format!(
+                        "Note #{} (remote #{}): {}",
+                        link.local_id, remote_id, e
+                    )),
+                }
+
+                continue;
+            }
+
+            // Remote note is new, or only changed on the server
+            let local_id = local_note.as_ref().and_then(|n| n.id);
+            match self.local.put_note(local_id, &remote_note) {
+                Ok(local) => {
+                    if let Some(id) = local.id {
+                        handled_local.insert(id);
+                    }
+                    links.insert(remote_id, new_link(&local, &remote_note)?);
+                    report.pulled += 1;
+                }
+                Err(e) => report
+                    .failures
+                    .push(format!("Remote note #{}: {}", remote_id, e)),
+            }
+        }
+
+        let linked_local: HashMap<i64, SyncLink> =
+            links.values().map(|l| (l.local_id, l.clone())).collect();
+
+        let mut local_ids: Vec<&i64> = local_notes.keys().collect();
+        local_ids.sort();
+
+        for local_id in local_ids {
+            if handled_local.contains(local_id) {
+                continue;
+            }
+
+            let local = &local_notes[local_id];
+            let link = linked_local.get(local_id);
+
+            if link.is_some_and(|l| l.local_updated_at == local.updated_at) {
+                continue;
+            }
+
+            let result = match link {
+                Some(link) => self.push_update(link.remote_id, local).await,
+                None => self.push_new(local).await,
+            };
+
+            match result {
+                Ok((local, remote)) => {
+                    let link = new_link(&local, &remote)?;
+                    links.insert(link.remote_id, link);
+                    report.pushed += 1;
+                }
+                Err(e) => report.failures.push(format!("Note #{}: {}", local_id, e)),
+            }
+        }
+
+        // Notes deleted locally since the last sync are deleted on the server as well
+        let deleted: Vec<SyncLink> = links
+            .values()
+            .filter(|l| !local_notes.contains_key(&l.local_id))
+            .filter(|l| !handled_local.contains(&l.local_id))
+            .cloned()
+            .collect();
+
+        for link in deleted {
+            match self.remote.delete(&[link.remote_id]).await {
+                Ok(()) => {
+                    links.remove(&link.remote_id);
+                    report.deleted += 1;
+                }
+                Err(e) => report
+                    .failures
+                    .push(format!("Remote note #{}: {}", link.remote_id, e)),
+            }
+        }
+
+        let mut links: Vec<SyncLink> = links.into_values().collect();
+        links.sort_by_key(|l| l.local_id);
+
+        state.remote = Some(remote_url);
+        state.links = links;
+        state.conflicts.extend(report.conflicts.iter().cloned());
+        if report.failures.is_empty() {
+            state.last_sync = Some(started_at);
+        }
+        state.save(&self.state_path)?;
+
+        Ok(report)
+    }
+
+    async fn push_update(&mut self, remote_id: i64, local: &Note) -> anyhow::Result<(Note, Note)> {
+        let remote = self
+            .remote
+            .update_note(
+                remote_id,
+                local.content.clone(),
+                local.tags.clone(),
+                local.target_date,
+            )
+            .await?;
+
+        Ok((local.clone(), remote))
+    }
+
+    async fn push_new(&mut self, local: &Note) -> anyhow::Result<(Note, Note)> {
+        let remote = self
+            .remote
+            .create_note(
+                local.content.clone(),
+                local.tags.clone(),
+                local
+                    .target_date
+                    .unwrap_or_else(|| local.created_at.date_naive()),
+            )
+            .await?;
+
+        Ok((local.clone(), remote))
+    }
+}
+
+/// The server filters by day in its own timezone, starting a day early covers any offset.
+/// Notes that were already synced come back too, but are skipped by their `updated_at`.
+fn updated_since(last_sync: DateTime<Utc>, limit: i64) -> NoteSearchArgs {
+    let since = last_sync.date_naive().pred_opt().unwrap_or(NaiveDate::MIN);
+
+    NoteSearchArgs {
+        filter: NoteFilterArgs {
+            updated: Some(DateFilter::Range(DateValue::Date(since), DateValue::Ever)),
+            limit: Some(limit),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn new_link(local: &Note, remote: &Note) -> anyhow::Result<SyncLink> {
+    Ok(SyncLink {
+        local_id: local.id.context("Local note without id")?,
+        remote_id: remote.id.context("Remote note without id")?,
+        local_updated_at: local.updated_at,
+        remote_updated_at: remote.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::date::zone::Zone, web_client::mock::MockClient};
+
+    #[tokio::test]
+    async fn test_full_incremental_search_lists_all_notes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state_path = dir.path().join(DEFAULT_SYNC_STATE_FILENAME);
+        let last_sync = DateTime::parse_from_rfc3339("2023-01-01T00:00:00Z")
+            .unwrap()
+            .into();
+        SyncState {
+            last_sync: Some(last_sync),
+            ..Default::default()
+        }
+        .save(&state_path)
+        .unwrap();
+
+        let mut remote = MockClient::new(None);
+        let all = remote.get_notes().await.unwrap().notes.len();
+        let mut local = LocalClient::new(&dir.path().join("store"), Zone::Local);
+        let mut sync = Synchronizer::new(&mut local, &mut remote, &state_path);
+        sync.updated_limit = 2;
+
+        let report = sync.sync(false).await.unwrap();
+
+        assert!(all > 2);
+        assert_eq!(report.fetched, all);
+        assert_eq!(report.pulled, all);
+        let state = SyncState::load(&state_path).unwrap();
+        assert!(state.last_sync.unwrap() > last_sync);
+    }
+}
@@ -12,3 +12,12 @@ pub fn contains_login_success_messages() -> impl Predicate<str> {
         .and(predicate::str::contains(r#"Mocking polling for token with device code:"#).count(2))
         .and(predicate::str::contains(r#"User successfully logged in."#))
 }
+
+pub fn contains_headless_login_success_messages() -> impl Predicate<str> {
+    predicate::str::contains(r#"Mocking sending device code"#)
+        .and(predicate::str::contains(
+            r#"Scan the code below or visit following URL to login"#,
+        ))
+        .and(predicate::str::contains(r#"Mocking polling for token with device code:"#).count(2))
+        .and(predicate::str::contains(r#"User successfully logged in."#))
+}
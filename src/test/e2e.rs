@@ -1,23 +1,2122 @@
 use predicate::str::is_empty;
 use predicates::prelude::*;
 
-use crate::web_client::mock::MOCK_TOKEN;
+use crate::{
+    model::StoredToken,
+    web_client::mock::{MOCK_ACCOUNT, MOCK_TOKEN, MOCK_USER_CODE},
+};
 
-use super::{asserts::contains_login_success_messages, test_context::TestContext};
+use super::{
+    asserts::{contains_headless_login_success_messages, contains_login_success_messages},
+    test_context::TestContext,
+};
 
 #[test]
 fn test_login() {
     // Arrange
     let ctx = TestContext::new("test_assets/profile/local.toml");
 
+    // Act
+    let assert = ctx
+        .command()
+        .env("DISPLAY", ":0")
+        .env_remove("SSH_CONNECTION")
+        // Stands in for the browser, so the test doesn't open a real one
+        .env("BROWSER", "true")
+        .arg("login")
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(contains_login_success_messages())
+        .stderr(is_empty());
+
+    ctx.assert_key_file_holds_token(MOCK_TOKEN);
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn test_login_headless() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .arg("login")
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(contains_headless_login_success_messages().and(predicate::str::contains("▀")))
+        .stderr(is_empty());
+
+    ctx.assert_key_file_holds_token(MOCK_TOKEN);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_note_edit() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let editor = ctx.editor_script("printf '\\nEdited\\n' >> \"$1\"");
+    let today = chrono::Utc::now().date_naive().to_string();
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "2"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("Multi-line note\nWith several")
+            .and(predicate::str::contains("To test preview\nEdited"))
+            .and(predicate::str::contains(format!("[{}]", today)))
+            .and(predicate::str::contains("Note #2 updated")),
+    );
+}
+
+#[test]
+fn test_note_edit_without_changes() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", "true")
+        .args(["note", "edit", "2"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("No changes, note #2 was not updated")
+            .and(predicate::str::contains("Note #2 updated").not()),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_note_edit_keeps_the_date() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let editor = ctx.editor_script(
+        "grep -v '^date' \"$1\" > \"$1.tmp\" && mv \"$1.tmp\" \"$1\" && printf '\\nEdited\\n' >> \"$1\"",
+    );
+    let today = chrono::Utc::now().date_naive().to_string();
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "2"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains(format!("[{}]", today))
+            .and(predicate::str::contains("Note #2 updated")),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_note_edit_reopens_on_invalid_date() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    // The first save clears the date, the second one fixes it after seeing the error
+    let editor = ctx.editor_script(
+        r#"if grep -q 'is not a day' "$1"; then
+  sed 's/^date = .*/date = "2024-03-16"/' "$1" > "$1.tmp"
+else
+  sed 's/^date = .*/date = ""/' "$1" > "$1.tmp"
+fi
+mv "$1.tmp" "$1""#,
+    );
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "2"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("[2024-03-16]").and(predicate::str::contains("Note #2 updated")),
+    );
+}
+
+#[test]
+fn test_note_edit_not_found() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", "true")
+        .args(["note", "edit", "99"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Note #99 not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_note_edit_rejected_by_the_server() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let editor = ctx.editor_script("printf '\\nEdited\\n' >> \"$1\"");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["--mock-param", "deleted_after_read:2", "note", "edit", "2"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains("Note #2 updated").not())
+        .stderr(predicate::str::contains("Note #2 not found"));
+}
+
+#[test]
+fn test_note_show() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "show", "3", "--output", "plain"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::starts_with("3;")
+            .and(predicate::str::contains("2024-01-03 10:00"))
+            .and(predicate::str::contains("* bullet point")),
+    );
+}
+
+#[test]
+fn test_note_show_in_profile_timezone() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/timezone.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "show", "3", "--output", "plain"])
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(predicate::str::contains("2024-01-03 19:00"));
+}
+
+#[test]
+fn test_note_search_pretty_shows_timestamps() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/timezone.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "search", "--output", "pretty"])
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(predicate::str::contains("created 2024-01-03 19:00"));
+}
+
+#[test]
+fn test_invalid_timezone() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.config_path, "timezone = \"Mars/Olympus\"\n").unwrap();
+
+    // Act
+    let assert = ctx.command().args(["note", "last"]).assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Unknown timezone 'Mars/Olympus'"));
+}
+
+#[test]
+fn test_note_show_json() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "show", "1", "--output", "json"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::starts_with("{")
+            .and(predicate::str::contains(r#""content": "Short note""#)),
+    );
+}
+
+#[test]
+fn test_note_show_not_found() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx.command().args(["note", "show", "99"]).assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Note #99 not found"));
+}
+
+#[test]
+fn test_note_delete() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "delete", "1", "2", "--yes"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("Deleted note #1")
+            .and(predicate::str::contains("Deleted note #2")),
+    );
+}
+
+#[test]
+fn test_note_delete_reports_failures() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["note", "delete", "1", "99", "--yes"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains("Deleted note #1"))
+        .stderr(
+            predicate::str::contains("Failed to delete note #99: Note #99 not found")
+                .and(predicate::str::contains("1 note(s) could not be deleted")),
+        );
+}
+
+#[test]
+fn test_note_search_delete_without_terminal() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let refused = ctx
+        .command()
+        .args(["note", "search", "--tag", "work", "--delete"])
+        .assert();
+    let deleted = ctx
+        .command()
+        .args(["note", "search", "--tag", "work", "--delete", "--yes"])
+        .assert();
+
+    // Assert
+    refused
+        .failure()
+        .stderr(predicate::str::contains("use --yes"));
+    deleted
+        .success()
+        .stdout(predicate::str::contains("Deleted note #"));
+}
+
+#[test]
+fn test_note_delete_rejected_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let delete = ctx
+        .command()
+        .args(["--mock-param", "no_delete", "note", "delete", "1", "--yes"])
+        .assert();
+    let search = ctx
+        .command()
+        .args([
+            "--mock-param",
+            "no_delete",
+            "note",
+            "search",
+            "--delete",
+            "--yes",
+        ])
+        .assert();
+
+    // Assert
+    delete
+        .code(77)
+        .stderr(predicate::str::contains("Login is not valid"));
+    search
+        .code(77)
+        .stderr(predicate::str::contains("Login is not valid"));
+}
+
+#[test]
+fn test_note_delete_requires_confirmation() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx.command().args(["note", "delete", "1"]).assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains("Deleted note").not())
+        .stderr(predicate::str::contains("use --yes"));
+}
+
+#[test]
+fn test_offline_note_is_queued_and_synced() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let outbox_path = ctx.temp_dir.path().join("outbox.json");
+
+    // Act
+    let queued = ctx
+        .command()
+        .args([
+            "--mock-param",
+            "offline",
+            "down",
+            "captured",
+            "on",
+            "a",
+            "plane",
+        ])
+        .assert();
+
+    // Assert
+    queued
+        .success()
+        .stdout(predicate::str::contains("note was queued (1 waiting)"));
+    let outbox = std::fs::read_to_string(&outbox_path).unwrap();
+    assert!(outbox.contains("captured on a plane"));
+
+    // Act
+    let synced = ctx.command().arg("sync").assert();
+
+    // Assert
+    synced.success().stdout(
+        predicate::str::contains("Synced note queued at")
+            .and(predicate::str::contains(": captured on a plane"))
+            .and(predicate::str::contains("1 note(s) synced, 0 still queued")),
+    );
+    let outbox = std::fs::read_to_string(&outbox_path).unwrap();
+    assert_eq!(outbox, "[]");
+}
+
+#[test]
+fn test_sync_stops_on_rejected_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let outbox_path = ctx.temp_dir.path().join("outbox.json");
+    for content in ["first", "second"] {
+        ctx.command()
+            .args(["--mock-param", "offline", "down", content])
+            .assert()
+            .success();
+    }
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["--mock-param", "no_create", "sync"])
+        .assert();
+
+    // Assert
+    assert
+        .code(77)
+        .stderr(predicate::str::contains("Login is not valid"));
+    let outbox = std::fs::read_to_string(&outbox_path).unwrap();
+    assert!(outbox.contains("first") && outbox.contains("second"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_is_not_queued_on_connect_errors() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let outbox_path = ctx.temp_dir.path().join("outbox.json");
+    let editor = ctx.editor_script("printf '\\nEdited\\n' >> \"$1\"");
+
+    // Act
+    let edit = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["--mock-param", "writes_offline", "note", "edit", "1"])
+        .assert();
+    let outbox_after_edit = outbox_path.exists();
+    let add = ctx
+        .command()
+        .args(["--mock-param", "writes_offline", "down", "new", "note"])
+        .assert();
+
+    // Assert
+    edit.failure().stderr(predicate::str::contains(
+        "Server is unreachable: mocked connection drop",
+    ));
+    assert!(!outbox_after_edit, "Edits should not be queued");
+    add.success()
+        .stdout(predicate::str::contains("note was queued (1 waiting)"));
+}
+
+#[test]
+fn test_sync_fails_while_offline() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["--mock-param", "offline", "sync"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Server is unreachable"));
+}
+
+#[test]
+fn test_local_backend() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+
+    ctx.unmocked_command()
+        .args(["down", "--tag", "work", "first", "note"])
+        .assert()
+        .success();
+    ctx.unmocked_command()
+        .args([
+            "down",
+            "--tag",
+            "home",
+            "--date",
+            "2024-01-01",
+            "second",
+            "note",
+        ])
+        .assert()
+        .success();
+
+    // Act
+    let by_tag = ctx
+        .unmocked_command()
+        .args(["note", "search", "--tag", "work", "--output", "plain"])
+        .assert();
+    let by_date = ctx
+        .unmocked_command()
+        .args([
+            "note",
+            "search",
+            "--date",
+            "..2024-12-31",
+            "--output",
+            "plain",
+        ])
+        .assert();
+    let by_term = ctx
+        .unmocked_command()
+        .args(["note", "search", "SECOND", "--output", "plain"])
+        .assert();
+    let deleted = ctx
+        .unmocked_command()
+        .args(["note", "delete", "1", "--yes"])
+        .assert();
+    let last = ctx
+        .unmocked_command()
+        .args(["note", "last", "--output", "plain"])
+        .assert();
+
+    // Assert
+    by_tag.success().stdout(
+        predicate::str::contains("first note").and(predicate::str::contains("second note").not()),
+    );
+    by_date.success().stdout(
+        predicate::str::contains("second note").and(predicate::str::contains("first note").not()),
+    );
+    by_term
+        .success()
+        .stdout(predicate::str::starts_with("2;2024-01-01;"));
+    deleted.success();
+    last.success()
+        .stdout(predicate::str::contains("second note"));
+    assert!(ctx.temp_dir.path().join("notes.json").exists());
+}
+
+#[test]
+fn test_local_backend_login_is_noop() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+
+    // Act
+    let assert = ctx.unmocked_command().arg("login").assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(predicate::str::contains("no login needed"));
+    assert!(!ctx.key_path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sync_with_remote() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+    let editor = ctx.editor_script("printf '\\nEdited\\n' >> \"$1\"");
+    let remote_path = ctx.temp_dir.path().join("remote.toml");
+    let state_path = ctx.temp_dir.path().join("sync_state.json");
+    let store_path = ctx.temp_dir.path().join("notes.json");
+    std::fs::write(&remote_path, r#"server_url = "http://localhost:9000""#).unwrap();
+    let remote = remote_path.to_str().unwrap();
+
+    // Act
+    let first = ctx.command().args(["sync", "--remote", remote]).assert();
+    let store_after_first = std::fs::metadata(&store_path).unwrap().modified().unwrap();
+    let second = ctx.command().args(["sync", "--remote", remote]).assert();
+    let store_after_second = std::fs::metadata(&store_path).unwrap().modified().unwrap();
+    ctx.unmocked_command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "2"])
+        .assert()
+        .success();
+    let third = ctx.command().args(["sync", "--remote", remote]).assert();
+
+    // Assert
+    first.success().stdout(
+        predicate::str::contains("3 pulled, 0 pushed, 0 deleted")
+            .and(predicate::str::contains("(3 fetched)")),
+    );
+    second.success().stdout(
+        predicate::str::contains("0 pulled, 0 pushed, 0 deleted")
+            .and(predicate::str::contains("(0 fetched)")),
+    );
+    assert_eq!(store_after_first, store_after_second);
+    third
+        .success()
+        .stdout(predicate::str::contains("0 pulled, 1 pushed, 0 deleted"));
+    let state = std::fs::read_to_string(&state_path).unwrap();
+    assert!(state.contains(r#""remote": "mocked_instance""#));
+}
+
+#[test]
+fn test_sync_with_remote_unlocks_encrypted_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+    let remote_path = ctx.temp_dir.path().join("remote.toml");
+    std::fs::write(
+        &remote_path,
+        "server_url = \"http://localhost:9000\"\nencrypt_token = true\n",
+    )
+    .unwrap();
+    let remote = remote_path.to_str().unwrap();
+    ctx.command()
+        .env("JOT_PROFILE", remote)
+        .env("JOT_PASSPHRASE", "correct horse")
+        .args(["login", "--with-token"])
+        .write_stdin("personal-token\n")
+        .assert()
+        .success();
+
+    // Act
+    let locked = ctx.command().args(["sync", "--remote", remote]).assert();
+    let unlocked = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .args(["sync", "--remote", remote])
+        .assert();
+
+    // Assert
+    locked.failure().stderr(predicate::str::contains(
+        "The token file is encrypted, set JOT_PASSPHRASE",
+    ));
+    unlocked
+        .success()
+        .stdout(predicate::str::contains("3 pulled, 0 pushed"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sync_with_remote_deletes_notes_deleted_on_the_server() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+    let editor = ctx.editor_script("printf '\\nEdited\\n' >> \"$1\"");
+    let remote_path = ctx.temp_dir.path().join("remote.toml");
+    std::fs::write(&remote_path, r#"server_url = "http://localhost:9000""#).unwrap();
+    let remote = remote_path.to_str().unwrap();
+
+    ctx.command()
+        .args(["sync", "--remote", remote])
+        .assert()
+        .success();
+    ctx.unmocked_command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "3"])
+        .assert()
+        .success();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args([
+            "--mock-param",
+            "deleted:2,3",
+            "sync",
+            "--remote",
+            remote,
+            "--full",
+        ])
+        .assert();
+    let search = ctx
+        .unmocked_command()
+        .args(["note", "search", "--output", "plain"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(predicate::str::contains(
+        "0 pulled, 1 pushed, 0 deleted on the server, 1 deleted locally",
+    ));
+    search.success().stdout(
+        predicate::str::contains("Short note")
+            .and(predicate::str::contains("Multi-line note").not())
+            .and(predicate::str::contains("special formatting")),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sync_with_remote_reports_conflicts() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+    let editor = ctx.temp_dir.path().join("editor.sh");
+    std::fs::write(
+        &editor,
+        "#!/bin/sh\nsed 's/Short note/Edited note/' \"$1\" > \"$1.tmp\" && mv \"$1.tmp\" \"$1\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&editor, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let remote_path = ctx.temp_dir.path().join("remote.toml");
+    let state_path = ctx.temp_dir.path().join("sync_state.json");
+    std::fs::write(&remote_path, r#"server_url = "http://localhost:9000""#).unwrap();
+    let remote = remote_path.to_str().unwrap();
+
+    ctx.command()
+        .args(["sync", "--remote", remote])
+        .assert()
+        .success();
+
+    // Pretend the server version of note #1 changed since a sync at the end of 2023
+    let mut state: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&state_path).unwrap()).unwrap();
+    state["last_sync"] = "2023-12-31T10:00:00Z".into();
+    std::fs::write(
+        &state_path,
+        state
+            .to_string()
+            .replace("2024-01-01T10:00:00Z", "2023-01-01T10:00:00Z"),
+    )
+    .unwrap();
+    ctx.unmocked_command()
+        .env("VISUAL", &editor)
+        .args(["note", "edit", "1"])
+        .assert()
+        .success();
+
+    // Act
+    let assert = ctx.command().args(["sync", "--remote", remote]).assert();
+    let state = std::fs::read_to_string(&state_path).unwrap();
+    let conflicts = ctx
+        .command()
+        .env("JOT_TIMEZONE", "Asia/Tokyo")
+        .args(["sync", "conflicts"])
+        .assert();
+    let missing = ctx.command().args(["sync", "resolve", "2"]).assert();
+    let resolved = ctx
+        .command()
+        .args(["sync", "resolve", "1", "--restore"])
+        .assert();
+    let no_conflicts = ctx.command().args(["sync", "conflicts"]).assert();
+    let restored = ctx
+        .unmocked_command()
+        .args(["note", "show", "1", "--output", "plain"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(predicate::str::contains(
+        "Conflict: note #1 (remote #1) changed on both sides, kept the local version",
+    ));
+    assert!(state.contains(r#""kept": "local""#));
+    assert!(state.contains("Short note"));
+    conflicts.success().stdout(
+        predicate::str::contains("1: note #1 (remote #1), kept the local version").and(
+            predicate::str::contains("discarded remote version from 2024-01-01 19:00: Short note"),
+        ),
+    );
+    missing
+        .failure()
+        .stderr(predicate::str::contains("No conflict 2"));
+    resolved.success().stdout(predicate::str::contains(
+        "Restored the remote version of note #1",
+    ));
+    no_conflicts
+        .success()
+        .stdout(predicate::str::contains("No sync conflicts"));
+    restored.success().stdout(
+        predicate::str::contains("Short note").and(predicate::str::contains("Edited note").not()),
+    );
+}
+
+#[test]
+fn test_sync_with_remote_requires_local_backend() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["sync", "--remote", "test_assets/profile/default.toml"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("backend = \"local\""));
+}
+
+#[test]
+fn test_export_markdown() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let export_dir = ctx.temp_dir.path().join("export");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["export", "markdown", export_dir.to_str().unwrap()])
+        .assert();
+    let again = ctx
+        .command()
+        .args(["export", "markdown", export_dir.to_str().unwrap()])
+        .assert();
+    let forced = ctx
+        .command()
+        .args(["export", "markdown", export_dir.to_str().unwrap(), "note"])
+        .args(["--tag", "tag3", "--force"])
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(predicate::str::contains("Exported 3 note(s)"));
+    again
+        .failure()
+        .stderr(predicate::str::contains("use --force to overwrite it"));
+    forced
+        .success()
+        .stdout(predicate::str::contains("Exported 3 note(s)"));
+
+    let mut files: Vec<_> = std::fs::read_dir(&export_dir)
+        .unwrap()
+        .map(|f| f.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+
+    assert_eq!(files.len(), 3);
+    assert!(files.iter().any(|f| f.ends_with("-multi-line-note.md")));
+
+    let content = std::fs::read_to_string(
+        export_dir.join(
+            files
+                .iter()
+                .find(|f| f.ends_with("-short-note.md"))
+                .unwrap(),
+        ),
+    )
+    .unwrap();
+    assert!(content.starts_with("id = 1\ntags = [\"tag1\", \"tag3\"]\n"));
+    assert!(content.contains("created_at = \"2024-01-01T10:00:00Z\""));
+    assert!(content.ends_with("+++\nShort note\n"));
+}
+
+#[test]
+fn test_import() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let notes_dir = ctx.temp_dir.path().join("notes");
+    std::fs::create_dir_all(notes_dir.join("nested")).unwrap();
+    std::fs::write(
+        notes_dir.join("with-front-matter.md"),
+        "tags = [\"work\"]\ndate = \"2024-03-16\"\n+++\nImported with tags\n",
+    )
+    .unwrap();
+    std::fs::write(notes_dir.join("nested/plain.md"), "Just a plain note\n").unwrap();
+    std::fs::write(notes_dir.join("ignored.json"), "{}").unwrap();
+    let notes_dir = notes_dir.to_str().unwrap();
+
+    // Act
+    let dry_run = ctx
+        .command()
+        .args(["import", notes_dir, "--dry-run"])
+        .assert();
+    let import = ctx.command().args(["import", notes_dir]).assert();
+    let again = ctx.command().args(["import", notes_dir]).assert();
+
+    // Assert
+    dry_run.success().stdout(
+        predicate::str::contains("[2024-03-16] work: Imported with tags").and(
+            predicate::str::contains("Would import 2 note(s), skipped 0, failed 0"),
+        ),
+    );
+    import.success().stdout(predicate::str::contains(
+        "Imported 2 note(s), skipped 0, failed 0",
+    ));
+    again.success().stdout(predicate::str::contains(
+        "Imported 0 note(s), skipped 2, failed 0",
+    ));
+}
+
+#[test]
+fn test_import_reports_errors() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let broken = ctx.temp_dir.path().join("broken.md");
+    let valid = ctx.temp_dir.path().join("valid.md");
+    std::fs::write(&broken, "tags = [\"unterminated\n+++\nContent\n").unwrap();
+    let no_day = ctx.temp_dir.path().join("no-day.md");
+    std::fs::write(&valid, "Valid note\n").unwrap();
+    std::fs::write(&no_day, "date = \"\"\n+++\nNote without a day\n").unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args([
+            "import",
+            broken.to_str().unwrap(),
+            valid.to_str().unwrap(),
+            no_day.to_str().unwrap(),
+        ])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains(
+            "Imported 1 note(s), skipped 0, failed 2",
+        ))
+        .stderr(
+            predicate::str::contains("Failed to parse")
+                .and(predicate::str::contains("broken.md"))
+                .and(predicate::str::contains("no-day.md"))
+                .and(predicate::str::contains("is not a day")),
+        );
+}
+
+#[test]
+fn test_import_stops_on_rejected_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let first = ctx.temp_dir.path().join("first.md");
+    let second = ctx.temp_dir.path().join("second.md");
+    std::fs::write(&first, "First note\n").unwrap();
+    std::fs::write(&second, "Second note\n").unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args([
+            "--mock-param",
+            "no_create",
+            "import",
+            first.to_str().unwrap(),
+            second.to_str().unwrap(),
+        ])
+        .assert();
+
+    // Assert
+    assert
+        .code(77)
+        .stdout(predicate::str::contains("failed").not())
+        .stderr(
+            predicate::str::contains("Login is not valid")
+                .and(predicate::str::contains("Failed to import").not()),
+        );
+}
+
+#[test]
+fn test_profile_flag_wins_over_env_profile_path() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let dir = ctx.profiles_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("work.toml"), r#"server_url = "http://work:9000""#).unwrap();
+    let work_path = dir.join("work.toml");
+
+    // Act
+    let by_flag = ctx
+        .named_profile_command()
+        .env("JOT_PROFILE", &ctx.config_path)
+        .args(["--profile", "work", "config", "--output", "plain"])
+        .assert();
+    let by_env = ctx
+        .named_profile_command()
+        .env("JOT_PROFILE", &ctx.config_path)
+        .args(["config", "--output", "plain"])
+        .assert();
+
+    // Assert
+    by_flag
+        .success()
+        .stdout(predicate::str::contains("server_url=http://work:9000").and(
+            predicate::str::contains(format!("profile_path={}", work_path.display())),
+        ));
+    by_env.success().stdout(predicate::str::contains(format!(
+        "profile_path={}",
+        ctx.config_path.display()
+    )));
+}
+
+#[test]
+fn test_named_profiles() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let dir = ctx.profiles_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("work.toml"), r#"server_url = "http://work:9000""#).unwrap();
+    std::fs::write(dir.join("home.toml"), r#"server_url = "http://home:9000""#).unwrap();
+    std::fs::write(dir.join("work.api_key"), "work-token").unwrap();
+
+    // Act
+    let switched = ctx
+        .named_profile_command()
+        .args(["profile", "use", "work"])
+        .assert();
+    let list = ctx
+        .named_profile_command()
+        .args(["profile", "list"])
+        .assert();
+    let config = ctx
+        .named_profile_command()
+        .args(["config", "--show-secrets"])
+        .assert();
+    let by_name = ctx
+        .named_profile_command()
+        .args(["--profile", "home", "config"])
+        .assert();
+    let renamed = ctx
+        .named_profile_command()
+        .args(["profile", "rename", "work", "office"])
+        .assert();
+    let list_renamed = ctx
+        .named_profile_command()
+        .args(["profile", "list"])
+        .assert();
+    let renamed_key = std::fs::read_to_string(dir.join("office.api_key")).unwrap();
+    let removed = ctx
+        .named_profile_command()
+        .args(["profile", "remove", "office", "--yes"])
+        .assert();
+
+    // Assert
+    switched
+        .success()
+        .stdout(predicate::str::contains("Switched to profile 'work'"));
+    list.success().stdout(
+        predicate::str::contains("* work (http://work:9000)")
+            .and(predicate::str::contains("  home (http://home:9000)")),
+    );
+    config.success().stdout(
+        predicate::str::contains(r#""server_url": "http://work:9000""#)
+            .and(predicate::str::contains("work.api_key"))
+            .and(predicate::str::contains(r#""token": "work-token""#)),
+    );
+    by_name.success().stdout(predicate::str::contains(
+        r#""server_url": "http://home:9000""#,
+    ));
+    renamed.success();
+    list_renamed
+        .success()
+        .stdout(predicate::str::contains("* office (http://work:9000)"));
+    assert_eq!(renamed_key, "work-token");
+    removed.success();
+    assert!(!dir.join("office.toml").exists());
+    assert!(!dir.join("office.api_key").exists());
+    assert!(dir.join("home.toml").exists());
+}
+
+#[test]
+fn test_named_profiles_ignore_unprefixed_files() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let dir = ctx.profiles_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("work.toml"), "backend = \"local\"\n").unwrap();
+    std::fs::write(dir.join("home.toml"), "backend = \"local\"\n").unwrap();
+    std::fs::write(dir.join("api_key"), "legacy-token").unwrap();
+    std::fs::write(dir.join("notes.json"), "legacy notes").unwrap();
+
+    // Act
+    let work = ctx
+        .named_profile_command()
+        .args(["--profile", "work", "config", "--show-secrets"])
+        .assert();
+    let home = ctx
+        .named_profile_command()
+        .args(["--profile", "home", "config", "--show-secrets"])
+        .assert();
+    let removed = ctx
+        .named_profile_command()
+        .args(["profile", "remove", "work", "--yes"])
+        .assert();
+    let renamed = ctx
+        .named_profile_command()
+        .args(["profile", "rename", "home", "office"])
+        .assert();
+
+    // Assert
+    for (assert, name) in [(work, "work"), (home, "home")] {
+        assert.success().stdout(
+            predicate::str::contains("\"token\": null")
+                .and(predicate::str::contains(format!("{}.api_key", name)))
+                .and(predicate::str::contains(format!("{}.notes.json", name))),
+        );
+    }
+    removed.success();
+    renamed.success();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("api_key")).unwrap(),
+        "legacy-token"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("notes.json")).unwrap(),
+        "legacy notes"
+    );
+    assert!(!dir.join("office.api_key").exists());
+}
+
+#[test]
+fn test_profile_add_without_terminal() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let dir = ctx.profiles_dir();
+
+    // Act
+    let prompted = ctx
+        .named_profile_command()
+        .args(["profile", "add", "lab"])
+        .assert();
+    let added = ctx
+        .named_profile_command()
+        .args([
+            "profile",
+            "add",
+            "lab",
+            "--yes",
+            "--server-url",
+            "http://lab:9000",
+        ])
+        .assert();
+    let exists = ctx
+        .named_profile_command()
+        .args(["profile", "add", "lab", "--yes"])
+        .assert();
+
+    // Assert
+    prompted
+        .failure()
+        .stderr(predicate::str::contains("Pass --yes"));
+    added.success();
+    let profile = std::fs::read_to_string(dir.join("lab.toml")).unwrap();
+    assert!(profile.contains(r#"server_url = "http://lab:9000""#));
+    assert!(profile.contains("lab.api_key"));
+    exists
+        .failure()
+        .stderr(predicate::str::contains("use --force to overwrite it"));
+}
+
+#[test]
+fn test_unknown_named_profile() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .named_profile_command()
+        .args(["--profile", "missing", "note", "last"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Profile 'missing' not found"));
+}
+
+#[test]
+fn test_config_set_get_unset() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let set = ctx
+        .command()
+        .args(["config", "set", "server_url", "https://jot.example.com"])
+        .assert();
+    let get = ctx.command().args(["config", "get", "server_url"]).assert();
+    let invalid = ctx
+        .command()
+        .args(["config", "set", "backend", "cloud"])
+        .assert();
+    let unset = ctx
+        .command()
+        .args(["config", "unset", "server_url"])
+        .assert();
+    let get_unset = ctx.command().args(["config", "get", "server_url"]).assert();
+
+    // Assert
+    set.success();
+    get.success()
+        .stdout(predicate::eq("https://jot.example.com\n"));
+    invalid
+        .failure()
+        .stderr(predicate::str::contains("Unknown backend 'cloud'"));
+    unset.success();
+    get_unset
+        .failure()
+        .stderr(predicate::str::contains("'server_url' is not set"));
+}
+
+#[test]
+fn test_config_set_changes_only_its_key() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(
+        &ctx.config_path,
+        "server_url = \"http://localhost:9000\"\ntimezone = \"Europe/Prague\"\n",
+    )
+    .unwrap();
+
+    // Act
+    let set = ctx
+        .command()
+        .args(["config", "set", "encrypt_token", "true"])
+        .assert();
+    let after_set = std::fs::read_to_string(&ctx.config_path).unwrap();
+    std::fs::write(&ctx.config_path, "future_key = 1\n").unwrap();
+    let unknown = ctx
+        .command()
+        .args(["config", "set", "server_url", "https://jot.example.com"])
+        .assert();
+    let after_unknown = std::fs::read_to_string(&ctx.config_path).unwrap();
+
+    // Assert
+    set.success();
+    assert!(after_set.contains("timezone = \"Europe/Prague\""));
+    assert!(after_set.contains("encrypt_token = true"));
+    unknown
+        .failure()
+        .stderr(predicate::str::contains("Unknown key 'future_key'"));
+    assert_eq!(after_unknown, "future_key = 1\n");
+}
+
+#[test]
+fn test_config_origin() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["--server-url", "http://other:9000", "config", "--origin"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("server_url            = http://other:9000 (arg)")
+            .and(predicate::str::contains(
+                "backend               = web (default)",
+            ))
+            .and(predicate::str::contains("(env)")),
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_config_edit_revalidates() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let editor = ctx.temp_dir.path().join("editor.sh");
+    std::fs::write(
+        &editor,
+        "#!/bin/sh\nprintf 'server_url = \"http://edited:9000\"\\ntimezone = \"Nowhere\"\\n' > \"$1\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&editor, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", &editor)
+        .args(["config", "edit"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Unknown timezone 'Nowhere'"));
+    let profile = std::fs::read_to_string(&ctx.config_path).unwrap();
+    assert!(!profile.contains("edited"));
+}
+
+#[test]
+fn test_config_masks_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, "secret-token").unwrap();
+
+    // Act
+    let masked = ctx.command().arg("config").assert();
+    let shown = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+
+    // Assert
+    masked.success().stdout(
+        predicate::str::contains(r#""token": "********""#)
+            .and(predicate::str::contains("secret-token").not()),
+    );
+    shown
+        .success()
+        .stdout(predicate::str::contains("\ntoken=secret-token\n"));
+}
+
+#[test]
+fn test_config_output_toml() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx.command().args(["config", "--output", "toml"]).assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains(r#"server_url = "http://localhost:9000""#)
+            .and(predicate::str::contains("\ntoken = ").not()),
+    );
+}
+
+#[test]
+fn test_project_config_is_layered() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local_backend.toml");
+    let project_dir = ctx.temp_dir.path().join("project");
+    let nested_dir = project_dir.join("src");
+    std::fs::create_dir_all(&nested_dir).unwrap();
+    std::fs::write(
+        project_dir.join(".jot.toml"),
+        "default_tags = [\"project-x\"]\ndefault_output = \"plain\"\n",
+    )
+    .unwrap();
+
+    // Act
+    ctx.unmocked_command()
+        .current_dir(&nested_dir)
+        .args(["down", "--tag", "work", "inside", "the", "project"])
+        .assert()
+        .success();
+    let last = ctx
+        .unmocked_command()
+        .current_dir(&nested_dir)
+        .args(["note", "last"])
+        .assert();
+    let config = ctx
+        .unmocked_command()
+        .current_dir(&nested_dir)
+        .args(["config", "--origin"])
+        .assert();
+
+    // Assert
+    last.success().stdout(predicate::str::contains(
+        ";work,project-x;inside the project",
+    ));
+    config.success().stdout(
+        predicate::str::contains("default_tags          = [\"project-x\"] (project)").and(
+            predicate::str::contains("default_output        = plain (project)"),
+        ),
+    );
+}
+
+#[test]
+fn test_project_config_cannot_redirect_the_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, "token").unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let project_dir = ctx.temp_dir.path().join("project");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    std::fs::write(
+        project_dir.join(".jot.toml"),
+        format!(
+            "server_url = \"http://{}\"\n",
+            listener.local_addr().unwrap()
+        ),
+    )
+    .unwrap();
+
+    // Act
+    let assert = ctx
+        .unmocked_command()
+        .current_dir(&project_dir)
+        .args(["note", "search"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("unknown field `server_url`"));
+    assert_eq!(
+        listener.accept().unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+}
+
+#[test]
+fn test_invalid_project_config_is_skipped_where_unused() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let project_dir = ctx.temp_dir.path().join("project");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    std::fs::write(
+        project_dir.join(".jot.toml"),
+        "server_url = \"http://example.com\"\n",
+    )
+    .unwrap();
+
+    // Act
+    let profiles = ctx
+        .named_profile_command()
+        .current_dir(&project_dir)
+        .args(["profile", "list"])
+        .assert();
+    let search = ctx
+        .command()
+        .current_dir(&project_dir)
+        .args(["note", "search"])
+        .assert();
+
+    // Assert
+    profiles.success().stderr(
+        predicate::str::contains("Warning: Invalid project config")
+            .and(predicate::str::contains(".jot.toml"))
+            .and(predicate::str::contains("it is ignored")),
+    );
+    search
+        .failure()
+        .stderr(predicate::str::contains("unknown field `server_url`"));
+}
+
+#[test]
+fn test_doctor() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, "token").unwrap();
+    #[cfg(unix)]
+    std::fs::set_permissions(
+        &ctx.key_path,
+        std::os::unix::fs::PermissionsExt::from_mode(0o600),
+    )
+    .unwrap();
+
+    // Act
+    let assert = ctx.command().env("VISUAL", "true").arg("doctor").assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("[ok]   Profile")
+            .and(predicate::str::contains("[ok]   Api key"))
+            .and(predicate::str::contains("answered in 3 ms"))
+            .and(predicate::str::contains("[ok]   Login"))
+            .and(predicate::str::contains("[ok]   Clock"))
+            .and(predicate::str::contains("[ok]   Editor: true"))
+            .and(predicate::str::contains("[fail]").not()),
+    );
+}
+
+#[test]
+fn test_doctor_reports_problems() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("VISUAL", "no-such-editor")
+        .args(["--mock-param", "offline", "doctor"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(
+            predicate::str::contains("[fail] Api key")
+                .and(predicate::str::contains("fix: Log in with 'jot login'"))
+                .and(predicate::str::contains("[fail] Server"))
+                .and(predicate::str::contains("[skip] Login"))
+                .and(predicate::str::contains(
+                    "[fail] Editor: 'no-such-editor' not found",
+                )),
+        )
+        .stderr(predicate::str::contains("3 check(s) failed"));
+}
+
+#[test]
+fn test_init_non_interactive() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let profile_path = ctx.temp_dir.path().join("provisioned/default.toml");
+
+    // Act
+    let created = ctx
+        .command()
+        .env("JOT_PROFILE", &profile_path)
+        .args(["init", "--yes", "--server-url", "https://jot.example.com"])
+        .assert();
+    let refused = ctx
+        .command()
+        .env("JOT_PROFILE", &profile_path)
+        .args(["init", "--yes", "--server-url", "https://other.example.com"])
+        .assert();
+    let forced = ctx
+        .command()
+        .env("JOT_PROFILE", &profile_path)
+        .args([
+            "init",
+            "--yes",
+            "--force",
+            "--server-url",
+            "https://other.example.com",
+            "--api-key-path",
+            "/keys/jot",
+        ])
+        .assert();
+
+    // Assert
+    created.success();
+    refused
+        .failure()
+        .stderr(predicate::str::contains("already exists, use --force"));
+    forced.success();
+    let profile = std::fs::read_to_string(&profile_path).unwrap();
+    assert!(profile.contains(r#"server_url = "https://other.example.com""#));
+    assert!(profile.contains(r#"api_key_path = "/keys/jot""#));
+}
+
+#[test]
+fn test_init_without_terminal_fails() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let profile_path = ctx.temp_dir.path().join("new.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("JOT_PROFILE", &profile_path)
+        .arg("init")
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("Not running in a terminal"));
+    assert!(!profile_path.exists());
+}
+
+#[test]
+fn test_env_overrides() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, "file-token").unwrap();
+
+    // Act
+    let from_env = ctx
+        .command()
+        .env("JOT_SERVER_URL", "http://env:9000")
+        .env("JOT_TOKEN", "env-token")
+        .env("JOT_OUTPUT", "json")
+        .env("JOT_LIMIT", "5")
+        .args(["config", "--show-secrets", "--origin"])
+        .assert();
+    let arg_wins = ctx
+        .command()
+        .env("JOT_SERVER_URL", "http://env:9000")
+        .args(["--server-url", "http://arg:9000", "config", "--origin"])
+        .assert();
+    let invalid = ctx
+        .command()
+        .env("JOT_BACKEND", "cloud")
+        .arg("config")
+        .assert();
+
+    // Assert
+    from_env.success().stdout(
+        predicate::str::contains("server_url            = http://env:9000 (env)")
+            .and(predicate::str::contains(
+                "token                 = env-token (env)",
+            ))
+            .and(predicate::str::contains(
+                "default_output        = json (env)",
+            ))
+            .and(predicate::str::contains("default_limit         = 5 (env)")),
+    );
+    arg_wins.success().stdout(predicate::str::contains(
+        "server_url            = http://arg:9000 (arg)",
+    ));
+    invalid
+        .failure()
+        .stderr(predicate::str::contains("Invalid JOT_BACKEND"));
+}
+
+#[test]
+fn test_logout() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, MOCK_TOKEN).unwrap();
+
+    // Act
+    let logout = ctx.command().arg("logout").assert();
+    let key_removed = !ctx.key_path.exists();
+    let again = ctx.command().arg("logout").assert();
+
+    // Assert
+    logout.success().stdout(
+        predicate::str::contains("Mock server: token revoked")
+            .and(predicate::str::contains("Logged out of")),
+    );
+    assert!(key_removed, "Key file should be deleted");
+    again
+        .success()
+        .stdout(predicate::str::contains("Already logged out of"));
+}
+
+/// Answers the first request with `status` and returns the server URL
+fn serve_status_once(status: &'static str) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = std::io::Read::read(&mut stream, &mut [0; 4096]);
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        std::io::Write::write_all(&mut stream, response.as_bytes()).unwrap();
+    });
+
+    url
+}
+
+#[test]
+fn test_logout_when_revoke_fails() {
+    for (status, warning) in [
+        ("404 Not Found", false),
+        ("500 Internal Server Error", true),
+    ] {
+        // Arrange
+        let ctx = TestContext::new("test_assets/profile/local.toml");
+        let url = serve_status_once(status);
+        std::fs::write(&ctx.config_path, format!("server_url = \"{}\"\n", url)).unwrap();
+        std::fs::write(&ctx.key_path, MOCK_TOKEN).unwrap();
+
+        // Act
+        let assert = ctx.unmocked_command().arg("logout").assert();
+
+        // Assert
+        let warned = predicate::str::contains("Couldn't revoke the token on the server");
+        let assert = assert
+            .success()
+            .stdout(predicate::str::contains(format!("Logged out of {}", url)));
+        if warning {
+            assert.stderr(warned);
+        } else {
+            assert.stderr(warned.not());
+        }
+        assert!(!ctx.key_path.exists(), "Key file should be deleted");
+    }
+}
+
+#[test]
+fn test_logout_all_profiles() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let dir = ctx.profiles_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("work.toml"), r#"server_url = "http://work:9000""#).unwrap();
+    std::fs::write(dir.join("home.toml"), r#"server_url = "http://home:9000""#).unwrap();
+    std::fs::write(dir.join("work.api_key"), "work-token").unwrap();
+    std::fs::write(dir.join("home.api_key"), "home-token").unwrap();
+
+    // Act
+    let assert = ctx
+        .named_profile_command()
+        .args(["logout", "--all-profiles"])
+        .assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("home: Mock server: token revoked")
+            .and(predicate::str::contains("work: Mock server: token revoked"))
+            .and(predicate::str::contains("Logged out of http://work:9000")),
+    );
+    assert!(!dir.join("work.api_key").exists());
+    assert!(!dir.join("home.api_key").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_token_file_formats() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, "legacy-token\n").unwrap();
+    std::fs::set_permissions(&ctx.key_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    // Act
+    let legacy = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+    std::fs::write(
+        &ctx.key_path,
+        r#"{"access_token": "json-token", "server_url": null, "issued_at": null, "expires_at": null}"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&ctx.key_path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    let json = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+
+    // Assert
+    legacy
+        .success()
+        .stdout(predicate::str::contains("token=legacy-token"))
+        .stderr(predicate::str::contains(
+            "is readable by other users (mode 644)",
+        ));
+    json.success()
+        .stdout(predicate::str::contains("token=json-token"))
+        .stderr(is_empty());
+}
+
+#[test]
+fn test_malformed_token_file_is_a_warning() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.key_path, r#"{"token": "json-token"}"#).unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+
+    // Assert
+    assert
+        .success()
+        .stdout(predicate::str::contains("\ntoken=\n"))
+        .stderr(predicate::str::contains("Warning: Invalid token file").and(
+            predicate::str::contains("expected an \"access_token\" or an encrypted token"),
+        ));
+}
+
+#[test]
+fn test_rejected_token_exits_with_dedicated_code() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let search = ctx
+        .command()
+        .args(["--mock-param", "unauthorized", "note", "search"])
+        .assert();
+    let add = ctx
+        .command()
+        .args(["--mock-param", "unauthorized", "down", "lost", "note"])
+        .assert();
+    let sync = ctx
+        .command()
+        .args(["--mock-param", "unauthorized", "sync"])
+        .assert();
+
+    // Assert
+    search.code(77).stderr(
+        predicate::str::contains("Login is not valid: mocked expired token")
+            .and(predicate::str::contains("Log in again with 'jot login'")),
+    );
+    add.code(77);
+    sync.code(77)
+        .stdout(predicate::str::contains("No queued notes").not());
+}
+
+#[test]
+fn test_login_with_device_grant() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(
+        &ctx.config_path,
+        "auth_flow = \"oauth\"\noauth_client_id = \"jot-cli\"\n",
+    )
+    .unwrap();
+
+    // Act
+    let assert = ctx.command().arg("login").assert();
+
+    // Assert
+    assert.success().stdout(
+        predicate::str::contains("Mocking device authorization for client: jot-cli")
+            .and(predicate::str::contains(format!(
+                "Your code: {}",
+                MOCK_USER_CODE
+            )))
+            .and(predicate::str::contains("it expires in 1 minute."))
+            .and(predicate::str::contains("User successfully logged in.")),
+    );
+    ctx.assert_key_file_holds_token(MOCK_TOKEN);
+}
+
+#[test]
+fn test_device_grant_denied() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(
+        &ctx.config_path,
+        "auth_flow = \"oauth\"\noauth_client_id = \"jot-cli\"\n",
+    )
+    .unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["--mock-param", "poll:pending,access_denied", "login"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains("User successfully logged in.").not())
+        .stderr(predicate::str::contains("Login was denied"));
+    assert!(!ctx.key_path.exists());
+}
+
+#[test]
+fn test_device_grant_expired() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(
+        &ctx.config_path,
+        "auth_flow = \"oauth\"\noauth_client_id = \"jot-cli\"\n",
+    )
+    .unwrap();
+
+    // Act
+    let assert = ctx
+        .command()
+        .args(["--mock-param", "poll:expired_token", "login"])
+        .assert();
+
+    // Assert
+    assert
+        .failure()
+        .stdout(predicate::str::contains("User successfully logged in.").not())
+        .stderr(
+            predicate::str::contains("Login failed").and(predicate::str::contains(
+                "The code expired before the login was finished",
+            )),
+        );
+    assert!(!ctx.key_path.exists());
+}
+
+#[test]
+fn test_device_grant_needs_client_id() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    std::fs::write(&ctx.config_path, "auth_flow = \"oauth\"\n").unwrap();
+
     // Act
     let assert = ctx.command().arg("login").assert();
 
+    // Assert
+    assert
+        .failure()
+        .stderr(predicate::str::contains("needs a client id"));
+}
+
+#[test]
+fn test_login_without_browser() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let assert = ctx
+        .command()
+        .env("DISPLAY", ":0")
+        .args(["login", "--no-browser"])
+        .assert();
+
     // Assert
     assert
         .success()
-        .stdout(contains_login_success_messages())
+        .stdout(
+            predicate::str::contains("Scan the code below or visit following URL to login")
+                .and(predicate::str::contains("▀"))
+                .and(predicate::str::contains("Could not open a browser").not())
+                .and(predicate::str::contains("User successfully logged in.")),
+        )
         .stderr(is_empty());
+    ctx.assert_key_file_holds_token(MOCK_TOKEN);
+}
+
+#[test]
+fn test_login_with_token() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+
+    // Act
+    let rejected = ctx
+        .command()
+        .args(["--mock-param", "unauthorized", "login", "--with-token"])
+        .write_stdin("expired-token\n")
+        .assert();
+    let rejected_saved = ctx.key_path.exists();
+    let empty = ctx
+        .command()
+        .args(["login", "--with-token"])
+        .write_stdin("\n")
+        .assert();
+    let accepted = ctx
+        .command()
+        .args(["login", "--with-token"])
+        .write_stdin("personal-token\n")
+        .assert();
+
+    // Assert
+    rejected
+        .code(77)
+        .stderr(predicate::str::contains("The token was not accepted"));
+    assert!(!rejected_saved, "Rejected token should not be saved");
+    empty
+        .failure()
+        .stderr(predicate::str::contains("No token given"));
+    accepted.success().stdout(predicate::str::contains(format!(
+        "Token is valid for {} on http://localhost:9000",
+        MOCK_ACCOUNT
+    )));
+    let stored: StoredToken =
+        serde_json::from_str(&std::fs::read_to_string(&ctx.key_path).unwrap()).unwrap();
+    assert_eq!(stored.access_token, "personal-token");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_api_key_commands() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    let stored_path = ctx.temp_dir.path().join("stored");
+    std::fs::write(
+        &ctx.config_path,
+        format!(
+            "api_key_command = \"printf 'command-token\\\\n'\"\napi_key_store_command = \"sh -c 'cat > {}'\"\n",
+            stored_path.display()
+        ),
+    )
+    .unwrap();
+
+    // Act
+    let config = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+    let login = ctx
+        .command()
+        .args(["login", "--with-token"])
+        .write_stdin("personal-token\n")
+        .assert();
+    let failing = ctx
+        .command()
+        .args(["config", "set", "api_key_command", "false"])
+        .assert();
+    let masked = ctx.command().args(["config", "--output", "plain"]).assert();
+    let after_failure = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+
+    // Assert
+    config
+        .success()
+        .stdout(predicate::str::contains("token=command-token"));
+    login.success().stdout(predicate::str::contains(
+        "Token stored with api_key_store_command",
+    ));
+    assert_eq!(
+        std::fs::read_to_string(&stored_path).unwrap(),
+        "personal-token\n"
+    );
+    assert!(!ctx.key_path.exists(), "Key file should not be written");
+    failing.success();
+    masked.success().stderr(is_empty());
+    after_failure
+        .success()
+        .stdout(predicate::str::contains("\ntoken=\n"))
+        .stderr(predicate::str::contains(
+            "Warning: api_key_command failed: 'false' failed with",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_doctor_checks_readable_encrypted_token() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    ctx.command()
+        .args(["config", "set", "encrypt_token", "true"])
+        .assert()
+        .success();
+    ctx.command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .args(["login", "--with-token"])
+        .write_stdin("personal-token\n")
+        .assert()
+        .success();
+    std::fs::set_permissions(&ctx.key_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+    // Act
+    let unlocked = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .arg("doctor")
+        .assert();
+    let wrong = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "battery staple")
+        .arg("doctor")
+        .assert();
+
+    // Assert
+    unlocked.stdout(
+        predicate::str::contains("is readable by other users (mode 644)").and(
+            predicate::str::contains("[ok]   Api key").and(predicate::str::contains("encrypted")),
+        ),
+    );
+    wrong.failure().stdout(
+        predicate::str::contains("is readable by other users (mode 644)")
+            .and(predicate::str::contains("couldn't be decrypted")),
+    );
+}
+
+#[test]
+fn test_encrypted_token_file() {
+    // Arrange
+    let ctx = TestContext::new("test_assets/profile/local.toml");
+    ctx.command()
+        .args(["config", "set", "encrypt_token", "true"])
+        .assert()
+        .success();
+
+    // Act
+    let login = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .args(["login", "--with-token"])
+        .write_stdin("personal-token\n")
+        .assert();
+    let key_file = std::fs::read_to_string(&ctx.key_path).unwrap();
+    let unlocked = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+    let wrong = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "battery staple")
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+    let missing = ctx
+        .command()
+        .args(["config", "--show-secrets", "--output", "plain"])
+        .assert();
+    let locked = ctx.command().args(["config", "--output", "plain"]).assert();
+    let search = ctx.command().args(["note", "search"]).assert();
+    let doctor = ctx
+        .command()
+        .env("JOT_PASSPHRASE", "correct horse")
+        .arg("doctor")
+        .assert();
 
-    ctx.assert_key_file_contains(MOCK_TOKEN.as_bytes());
+    // Assert
+    login.success();
+    assert!(key_file.contains("argon2id"));
+    assert!(!key_file.contains("personal-token"));
+    unlocked.success().stdout(
+        predicate::str::contains("\ntoken=personal-token\n")
+            .and(predicate::str::contains("token_encrypted=true")),
+    );
+    wrong.success().stderr(predicate::str::contains(
+        "Warning: Couldn't decrypt the token: Wrong passphrase",
+    ));
+    missing
+        .success()
+        .stderr(predicate::str::contains("set JOT_PASSPHRASE"));
+    locked
+        .success()
+        .stdout(predicate::str::contains("token_encrypted=true"))
+        .stderr(is_empty());
+    search
+        .failure()
+        .stderr(predicate::str::contains("set JOT_PASSPHRASE"));
+    doctor.stdout(
+        predicate::str::contains("[ok]   Api key")
+            .and(predicate::str::contains("api_key\", encrypted")),
+    );
 }
@@ -1,11 +1,8 @@
-use std::path::Path;
-
 use assert_cmd::Command;
 use predicates::prelude::{
     predicate::str::{contains, is_empty},
     PredicateBooleanExt,
 };
-use tempfile::TempDir;
 
 pub mod asserts;
 mod e2e;
@@ -17,7 +14,7 @@ fn test_profile_arg() {
 
     let assert = cmd
         .env("JOT_PROFILE", "bad_test.toml")
-        .args(&["--profile-path", "test_assets/profile/default.toml"])
+        .args(["--profile-path", "test_assets/profile/default.toml"])
         .arg("config")
         .assert();
 
@@ -2,6 +2,8 @@ use assert_cmd::Command;
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
+use crate::model::StoredToken;
+
 pub struct TestContext {
     pub temp_dir: TempDir,
     pub config_path: PathBuf,
@@ -12,7 +14,7 @@ impl TestContext {
     pub fn new(toml_path: &str) -> Self {
         let temp_dir = TempDir::new().unwrap();
         let dir_path = temp_dir.path();
-        let config_path = dir_path.join(Path::new("local.toml"));
+        let config_path = dir_path.join(Path::new("default.toml"));
         let key_path = dir_path.join(Path::new("api_key"));
 
         // Copy test config if needed
@@ -28,13 +30,70 @@ impl TestContext {
     pub fn command(&self) -> Command {
         let mut cmd = Command::cargo_bin("jot-cli").unwrap();
         cmd.env("JOT_PROFILE", self.config_path.to_str().unwrap())
+            .env("TZ", "UTC") // Profiles without a timezone use the local one
             .arg("-m"); // Always use mock mode in tests
         cmd
     }
 
-    pub fn assert_key_file_contains(&self, expected_content: &[u8]) {
+    /// Command running against the backend configured in the profile
+    pub fn unmocked_command(&self) -> Command {
+        let mut cmd = Command::cargo_bin("jot-cli").unwrap();
+        cmd.env("JOT_PROFILE", self.config_path.to_str().unwrap())
+            .env("TZ", "UTC");
+        cmd
+    }
+
+    /// Command resolving profiles by name from `profiles_dir` instead of a fixed path
+    pub fn named_profile_command(&self) -> Command {
+        let mut cmd = Command::cargo_bin("jot-cli").unwrap();
+        cmd.env_remove("JOT_PROFILE")
+            .env("XDG_CONFIG_HOME", self.temp_dir.path().join("config"))
+            .env("TZ", "UTC")
+            .arg("-m");
+        cmd
+    }
+
+    /// Shell script usable as VISUAL, it gets the edited file as `$1`
+    #[cfg(unix)]
+    pub fn editor_script(&self, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let editor = self.temp_dir.path().join("editor.sh");
+        std::fs::write(&editor, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+        editor
+    }
+
+    /// Directory holding named profiles for `named_profile_command`
+    pub fn profiles_dir(&self) -> PathBuf {
+        self.temp_dir.path().join("config").join("jot")
+    }
+
+    pub fn assert_key_file_holds_token(&self, expected_token: &str) {
         assert!(self.key_path.exists(), "Key file should exist");
-        let content = std::fs::read(&self.key_path).unwrap();
-        assert_eq!(content, expected_content, "Key file content mismatch");
+        let content = std::fs::read_to_string(&self.key_path).unwrap();
+        let stored: StoredToken = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            stored.access_token, expected_token,
+            "Key file token mismatch"
+        );
+        assert!(
+            stored.issued_at.is_some(),
+            "Key file should record the issue time"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&self.key_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(
+                mode & 0o777,
+                0o600,
+                "Key file should be readable by owner only"
+            );
+        }
     }
 }
@@ -2,7 +2,7 @@ use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
-use super::date_value::DateValue;
+use super::date_value::{parse_period_str, DateValue};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DateFilter {
@@ -16,20 +16,39 @@ impl FromStr for DateFilter {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.split("..").collect();
         match parts.len() {
-            1 => Ok(Self::SpecificDate(parts[0].parse()?)),
-            2 => Ok(Self::Range(parts[0].parse()?, parts[1].parse()?)),
+            1 => match parse_period_str(parts[0]) {
+                // Periods like "last week" cover every day from their start to their end
+                Some((unit, relative)) => Ok(Self::Range(
+                    DateValue::StartOf(unit, relative),
+                    DateValue::EndOf(unit, relative),
+                )),
+                None => Ok(Self::SpecificDate(parts[0].parse()?)),
+            },
+            2 => Ok(Self::Range(
+                parse_bound(parts[0], true)?,
+                parse_bound(parts[1], false)?,
+            )),
             _ => Err(anyhow::anyhow!("Invalid date filter")),
         }
     }
 }
 
+/// Periods used as a range bound stand for their first day on the left and last day on the right
+fn parse_bound(s: &str, is_start: bool) -> anyhow::Result<DateValue> {
+    match parse_period_str(s) {
+        Some((unit, relative)) if is_start => Ok(DateValue::StartOf(unit, relative)),
+        Some((unit, relative)) => Ok(DateValue::EndOf(unit, relative)),
+        None => s.parse(),
+    }
+}
+
 #[cfg(test)]
 mod date_value_test {
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, Weekday};
 
     use crate::utils::date::date_filter::DateFilter;
 
-    use crate::utils::date::date_value::DateValue;
+    use crate::utils::date::date_value::{DateUnit, DateValue, Relative};
 
     #[test]
     fn test_specific_ever() {
@@ -93,4 +112,119 @@ mod date_value_test {
             DateFilter::Range(DateValue::WeekAgo, DateValue::InAWeek)
         );
     }
+
+    #[test]
+    fn test_this_week() {
+        let value = "this week".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(
+                DateValue::StartOf(DateUnit::Week, Relative::This),
+                DateValue::EndOf(DateUnit::Week, Relative::This)
+            )
+        );
+    }
+
+    #[test]
+    fn test_last_week() {
+        let value = "last week".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(
+                DateValue::StartOf(DateUnit::Week, Relative::Last),
+                DateValue::EndOf(DateUnit::Week, Relative::Last)
+            )
+        );
+    }
+
+    #[test]
+    fn test_next_month() {
+        let value = "Next Month".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(
+                DateValue::StartOf(DateUnit::Month, Relative::Next),
+                DateValue::EndOf(DateUnit::Month, Relative::Next)
+            )
+        );
+    }
+
+    #[test]
+    fn test_last_year() {
+        let value = "last year".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(
+                DateValue::StartOf(DateUnit::Year, Relative::Last),
+                DateValue::EndOf(DateUnit::Year, Relative::Last)
+            )
+        );
+    }
+
+    #[test]
+    fn test_specific_relative_day() {
+        let value = "3 days ago".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::SpecificDate(DateValue::Offset(-3, DateUnit::Day))
+        );
+    }
+
+    #[test]
+    fn test_specific_weekday() {
+        let value = "next friday".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::SpecificDate(DateValue::Weekday(Weekday::Fri, Relative::Next))
+        );
+    }
+
+    #[test]
+    fn test_range_of_periods() {
+        let value = "last month..this week".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(
+                DateValue::StartOf(DateUnit::Month, Relative::Last),
+                DateValue::EndOf(DateUnit::Week, Relative::This)
+            )
+        );
+    }
+
+    #[test]
+    fn test_range_relative_days() {
+        let value = "2 weeks ago..yesterday".parse::<DateFilter>().unwrap();
+
+        assert_eq!(
+            value,
+            DateFilter::Range(DateValue::Offset(-2, DateUnit::Week), DateValue::Yesterday)
+        );
+    }
+
+    #[test]
+    fn test_period_resolves_to_days() {
+        // Wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 3, 13).unwrap();
+
+        let DateFilter::Range(from, until) = "last week".parse::<DateFilter>().unwrap() else {
+            panic!("Expected a range");
+        };
+
+        assert_eq!(from.to_date(today), NaiveDate::from_ymd_opt(2024, 3, 4));
+        assert_eq!(until.to_date(today), NaiveDate::from_ymd_opt(2024, 3, 10));
+    }
+
+    #[test]
+    fn test_invalid_filter() {
+        assert!("whenever".parse::<DateFilter>().is_err());
+        assert!("today..someday".parse::<DateFilter>().is_err());
+        assert!("a..b..c".parse::<DateFilter>().is_err());
+    }
 }
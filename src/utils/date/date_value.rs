@@ -1,6 +1,6 @@
 use std::{fmt::Display, str::FromStr};
 
-use chrono::{Days, Months, NaiveDate};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -17,13 +17,40 @@ pub enum DateValue {
     YearAgo,
     InAYear,
     Ever,
+    /// Number of units from today, negative values point to the past ("3 days ago", "in 2 weeks")
+    Offset(i64, DateUnit),
+    /// Weekday relative to today ("last friday", "this monday", "next sunday")
+    Weekday(Weekday, Relative),
+    /// First day of a week, month or year ("start of last month")
+    StartOf(DateUnit, Relative),
+    /// Last day of a week, month or year ("end of this year")
+    EndOf(DateUnit, Relative),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relative {
+    Last,
+    This,
+    Next,
 }
 
 impl FromStr for DateValue {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
+        let normalized = normalize(s);
+
+        match normalized.as_str() {
             "today" => Ok(Self::Today),
             "tomorrow" => Ok(Self::Tomorrow),
             "yesterday" => Ok(Self::Yesterday),
@@ -34,11 +61,109 @@ impl FromStr for DateValue {
             "year ago" => Ok(Self::YearAgo),
             "in a year" => Ok(Self::InAYear),
             "" => Ok(Self::Ever),
-            _ => Ok(Self::Date(NaiveDate::parse_from_str(s, "%Y-%m-%d")?)),
+            _ => parse_phrase(&normalized),
+        }
+    }
+}
+
+fn parse_phrase(s: &str) -> anyhow::Result<DateValue> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(DateValue::Date(date));
+    }
+
+    let words: Vec<&str> = s.split(' ').collect();
+
+    let value = match words.as_slice() {
+        [amount, unit, "ago"] => parse_offset(amount, unit, -1),
+        ["in", amount, unit] => parse_offset(amount, unit, 1),
+        [amount, unit, "from", "now"] => parse_offset(amount, unit, 1),
+        [weekday] => parse_weekday(weekday).map(|w| DateValue::Weekday(w, Relative::This)),
+        [relative, weekday] if parse_weekday(weekday).is_some() => parse_relative(relative)
+            .zip(parse_weekday(weekday))
+            .map(|(r, w)| DateValue::Weekday(w, r)),
+        ["start" | "beginning", "of", relative, unit] => {
+            parse_period(relative, unit).map(|(u, r)| DateValue::StartOf(u, r))
+        }
+        ["end", "of", relative, unit] => {
+            parse_period(relative, unit).map(|(u, r)| DateValue::EndOf(u, r))
         }
+        [relative, unit] if parse_period(relative, unit).is_some() => {
+            anyhow::bail!(
+                "'{}' is a period, not a single day. Use it as a filter, or 'start of {}'",
+                s,
+                s
+            )
+        }
+        _ => None,
+    };
+
+    value.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unrecognized date '{}', use YYYY-MM-DD or phrases like 'today', '3 days ago', 'next friday' or 'start of last month'",
+            s
+        )
+    })
+}
+
+/// Parses periods like "last week" or "this month" that span more than one day
+pub fn parse_period_str(s: &str) -> Option<(DateUnit, Relative)> {
+    let normalized = normalize(s);
+    let words: Vec<&str> = normalized.split(' ').collect();
+
+    match words.as_slice() {
+        [relative, unit] => parse_period(relative, unit),
+        _ => None,
+    }
+}
+
+fn parse_period(relative: &str, unit: &str) -> Option<(DateUnit, Relative)> {
+    let unit = match unit {
+        "week" => DateUnit::Week,
+        "month" => DateUnit::Month,
+        "year" => DateUnit::Year,
+        _ => return None,
+    };
+
+    parse_relative(relative).map(|r| (unit, r))
+}
+
+fn parse_offset(amount: &str, unit: &str, sign: i64) -> Option<DateValue> {
+    let amount = match amount {
+        "a" | "an" | "one" => 1,
+        _ => amount.parse::<i64>().ok().filter(|a| *a >= 0)?,
+    };
+
+    let unit = match unit {
+        "day" | "days" => DateUnit::Day,
+        "week" | "weeks" => DateUnit::Week,
+        "month" | "months" => DateUnit::Month,
+        "year" | "years" => DateUnit::Year,
+        _ => return None,
+    };
+
+    Some(DateValue::Offset(sign * amount, unit))
+}
+
+fn parse_relative(relative: &str) -> Option<Relative> {
+    match relative {
+        "last" | "previous" => Some(Relative::Last),
+        "this" => Some(Relative::This),
+        "next" => Some(Relative::Next),
+        _ => None,
     }
 }
 
+fn parse_weekday(weekday: &str) -> Option<Weekday> {
+    weekday.parse::<Weekday>().ok()
+}
+
+fn normalize(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
 impl DateValue {
     pub fn to_date(&self, today: NaiveDate) -> Option<NaiveDate> {
         match self {
@@ -53,10 +178,89 @@ impl DateValue {
             Self::InAYear => today.checked_add_months(Months::new(12)),
             Self::Ever => None,
             Self::Date(date) => Some(*date),
+            Self::Offset(amount, unit) => add_units(today, *amount, *unit),
+            Self::Weekday(weekday, relative) => {
+                let days_from_today = weekday.num_days_from_monday() as i64
+                    - today.weekday().num_days_from_monday() as i64;
+
+                let days = match relative {
+                    Relative::This => days_from_today,
+                    Relative::Next if days_from_today > 0 => days_from_today,
+                    Relative::Next => days_from_today + 7,
+                    Relative::Last if days_from_today < 0 => days_from_today,
+                    Relative::Last => days_from_today - 7,
+                };
+
+                add_units(today, days, DateUnit::Day)
+            }
+            Self::StartOf(unit, relative) => period_start(today, *unit, *relative),
+            Self::EndOf(unit, relative) => {
+                let next_start = period_start(today, *unit, *relative)
+                    .and_then(|start| add_units(start, 1, *unit))?;
+
+                next_start.pred_opt()
+            }
+        }
+    }
+}
+
+fn add_units(date: NaiveDate, amount: i64, unit: DateUnit) -> Option<NaiveDate> {
+    let days = |n: i64| Days::new(n.unsigned_abs());
+    let months = |n: i64| u32::try_from(n.unsigned_abs()).ok().map(Months::new);
+
+    match unit {
+        DateUnit::Day | DateUnit::Week => {
+            let amount = if unit == DateUnit::Week {
+                amount.checked_mul(7)?
+            } else {
+                amount
+            };
+
+            if amount < 0 {
+                date.checked_sub_days(days(amount))
+            } else {
+                date.checked_add_days(days(amount))
+            }
+        }
+        DateUnit::Month | DateUnit::Year => {
+            let amount = if unit == DateUnit::Year {
+                amount.checked_mul(12)?
+            } else {
+                amount
+            };
+
+            if amount < 0 {
+                date.checked_sub_months(months(amount)?)
+            } else {
+                date.checked_add_months(months(amount)?)
+            }
         }
     }
 }
 
+/// First day of the period containing `today`, shifted by one period for last/next.
+/// Weeks start on Monday.
+fn period_start(today: NaiveDate, unit: DateUnit, relative: Relative) -> Option<NaiveDate> {
+    let start = match unit {
+        DateUnit::Day => today,
+        DateUnit::Week => add_units(
+            today,
+            -(today.weekday().num_days_from_monday() as i64),
+            DateUnit::Day,
+        )?,
+        DateUnit::Month => today.with_day(1)?,
+        DateUnit::Year => today.with_day(1)?.with_month(1)?,
+    };
+
+    let shift = match relative {
+        Relative::Last => -1,
+        Relative::This => 0,
+        Relative::Next => 1,
+    };
+
+    add_units(start, shift, unit)
+}
+
 impl Display for DateValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -71,8 +275,264 @@ impl Display for DateValue {
             Self::InAYear => f.write_str("in a year")?,
             Self::Ever => f.write_str("")?,
             Self::Date(date) => f.write_str(&date.format("%Y-%m-%d").to_string())?,
+            Self::Offset(amount, unit) => {
+                let plural = if amount.abs() == 1 { "" } else { "s" };
+
+                if *amount < 0 {
+                    write!(f, "{} {}{} ago", amount.abs(), unit, plural)?
+                } else {
+                    write!(f, "in {} {}{}", amount, unit, plural)?
+                }
+            }
+            Self::Weekday(weekday, relative) => {
+                write!(f, "{} {}", relative, weekday_name(*weekday))?
+            }
+            Self::StartOf(unit, relative) => write!(f, "start of {} {}", relative, unit)?,
+            Self::EndOf(unit, relative) => write!(f, "end of {} {}", relative, unit)?,
         };
 
         Ok(())
     }
 }
+
+impl Display for DateUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        })
+    }
+}
+
+impl Display for Relative {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Last => "last",
+            Self::This => "this",
+            Self::Next => "next",
+        })
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+#[cfg(test)]
+mod date_value_test {
+    use chrono::{NaiveDate, Weekday};
+
+    use super::{DateUnit, DateValue, Relative};
+
+    // Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, 13).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn test_legacy_phrases() {
+        assert_eq!("week ago".parse::<DateValue>().unwrap(), DateValue::WeekAgo);
+        assert_eq!(
+            "in a month".parse::<DateValue>().unwrap(),
+            DateValue::InAMonth
+        );
+        assert_eq!("".parse::<DateValue>().unwrap(), DateValue::Ever);
+    }
+
+    #[test]
+    fn test_case_and_whitespace() {
+        assert_eq!(" Today ".parse::<DateValue>().unwrap(), DateValue::Today);
+        assert_eq!(
+            "3  Days   AGO".parse::<DateValue>().unwrap(),
+            DateValue::Offset(-3, DateUnit::Day)
+        );
+    }
+
+    #[test]
+    fn test_specific_date() {
+        let value = "2024-01-01".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Date(date(2024, 1, 1).unwrap()));
+    }
+
+    #[test]
+    fn test_days_ago() {
+        let value = "3 days ago".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Offset(-3, DateUnit::Day));
+        assert_eq!(value.to_date(today()), date(2024, 3, 10));
+    }
+
+    #[test]
+    fn test_a_week_ago() {
+        let value = "a week ago".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Offset(-1, DateUnit::Week));
+        assert_eq!(value.to_date(today()), date(2024, 3, 6));
+    }
+
+    #[test]
+    fn test_in_weeks() {
+        let value = "in 2 weeks".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Offset(2, DateUnit::Week));
+        assert_eq!(value.to_date(today()), date(2024, 3, 27));
+    }
+
+    #[test]
+    fn test_months_from_now() {
+        let value = "1 month from now".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Offset(1, DateUnit::Month));
+        assert_eq!(value.to_date(today()), date(2024, 4, 13));
+    }
+
+    #[test]
+    fn test_years_ago() {
+        let value = "2 years ago".parse::<DateValue>().unwrap();
+
+        assert_eq!(value.to_date(today()), date(2022, 3, 13));
+    }
+
+    #[test]
+    fn test_month_offset_clamps_to_month_end() {
+        let value = "1 month ago".parse::<DateValue>().unwrap();
+
+        assert_eq!(
+            value.to_date(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()),
+            date(2024, 2, 29)
+        );
+    }
+
+    #[test]
+    fn test_bare_weekday() {
+        let value = "friday".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Weekday(Weekday::Fri, Relative::This));
+        assert_eq!(value.to_date(today()), date(2024, 3, 15));
+        assert_eq!(
+            "mon".parse::<DateValue>().unwrap().to_date(today()),
+            date(2024, 3, 11)
+        );
+    }
+
+    #[test]
+    fn test_next_weekday() {
+        let value = "next friday".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Weekday(Weekday::Fri, Relative::Next));
+        assert_eq!(value.to_date(today()), date(2024, 3, 15));
+        assert_eq!(
+            "next wednesday"
+                .parse::<DateValue>()
+                .unwrap()
+                .to_date(today()),
+            date(2024, 3, 20)
+        );
+        assert_eq!(
+            "next monday".parse::<DateValue>().unwrap().to_date(today()),
+            date(2024, 3, 18)
+        );
+    }
+
+    #[test]
+    fn test_last_weekday() {
+        let value = "last friday".parse::<DateValue>().unwrap();
+
+        assert_eq!(value, DateValue::Weekday(Weekday::Fri, Relative::Last));
+        assert_eq!(value.to_date(today()), date(2024, 3, 8));
+        assert_eq!(
+            "last wednesday"
+                .parse::<DateValue>()
+                .unwrap()
+                .to_date(today()),
+            date(2024, 3, 6)
+        );
+        assert_eq!(
+            "previous monday"
+                .parse::<DateValue>()
+                .unwrap()
+                .to_date(today()),
+            date(2024, 3, 11)
+        );
+    }
+
+    #[test]
+    fn test_start_and_end_of_week() {
+        let start = "start of last week".parse::<DateValue>().unwrap();
+        let end = "end of this week".parse::<DateValue>().unwrap();
+
+        assert_eq!(start, DateValue::StartOf(DateUnit::Week, Relative::Last));
+        assert_eq!(start.to_date(today()), date(2024, 3, 4));
+        assert_eq!(end, DateValue::EndOf(DateUnit::Week, Relative::This));
+        assert_eq!(end.to_date(today()), date(2024, 3, 17));
+    }
+
+    #[test]
+    fn test_start_and_end_of_month() {
+        let start = "beginning of next month".parse::<DateValue>().unwrap();
+        let end = "end of last month".parse::<DateValue>().unwrap();
+
+        assert_eq!(start.to_date(today()), date(2024, 4, 1));
+        assert_eq!(end.to_date(today()), date(2024, 2, 29));
+    }
+
+    #[test]
+    fn test_start_and_end_of_year() {
+        let start = "start of this year".parse::<DateValue>().unwrap();
+        let end = "end of next year".parse::<DateValue>().unwrap();
+
+        assert_eq!(start.to_date(today()), date(2024, 1, 1));
+        assert_eq!(end.to_date(today()), date(2025, 12, 31));
+    }
+
+    #[test]
+    fn test_period_is_not_a_date() {
+        let error = "last week".parse::<DateValue>().unwrap_err();
+
+        assert!(error.to_string().contains("is a period"));
+    }
+
+    #[test]
+    fn test_invalid_phrases() {
+        assert!("someday".parse::<DateValue>().is_err());
+        assert!("-3 days ago".parse::<DateValue>().is_err());
+        assert!("3 fortnights ago".parse::<DateValue>().is_err());
+        assert!("start of last day".parse::<DateValue>().is_err());
+        assert!("2024-02-30".parse::<DateValue>().is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let values = [
+            DateValue::Today,
+            DateValue::WeekAgo,
+            DateValue::Date(date(2024, 1, 1).unwrap()),
+            DateValue::Offset(-1, DateUnit::Day),
+            DateValue::Offset(-3, DateUnit::Week),
+            DateValue::Offset(2, DateUnit::Month),
+            DateValue::Weekday(Weekday::Sat, Relative::Last),
+            DateValue::Weekday(Weekday::Tue, Relative::This),
+            DateValue::StartOf(DateUnit::Month, Relative::Next),
+            DateValue::EndOf(DateUnit::Year, Relative::Last),
+        ];
+
+        for value in values {
+            assert_eq!(value.to_string().parse::<DateValue>().unwrap(), value);
+        }
+    }
+}
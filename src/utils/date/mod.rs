@@ -1,2 +1,3 @@
 pub mod date_filter;
 pub mod date_value;
+pub mod zone;
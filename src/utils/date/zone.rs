@@ -0,0 +1,117 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Serialize, Serializer};
+
+/// Timezone used to resolve relative dates and to display timestamps
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Zone {
+    /// Timezone of this machine
+    #[default]
+    Local,
+    /// IANA timezone, e.g. `Europe/Prague`
+    Named(Tz),
+}
+
+impl Zone {
+    /// Current date in this zone, used in place of `DateValue::Today`
+    pub fn today(&self) -> NaiveDate {
+        self.date_of(&Utc::now())
+    }
+
+    /// Calendar date of an instant as seen in this zone
+    pub fn date_of(&self, instant: &DateTime<Utc>) -> NaiveDate {
+        match self {
+            Zone::Local => instant.with_timezone(&Local).date_naive(),
+            Zone::Named(tz) => instant.with_timezone(tz).date_naive(),
+        }
+    }
+
+    pub fn format(&self, instant: &DateTime<Utc>, format: &str) -> String {
+        match self {
+            Zone::Local => instant.with_timezone(&Local).format(format).to_string(),
+            Zone::Named(tz) => instant.with_timezone(tz).format(format).to_string(),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+
+        s.parse::<Tz>().map(Zone::Named).map_err(|_| {
+            anyhow::anyhow!(
+                "Unknown timezone '{}', use an IANA name like 'Europe/Prague'",
+                s
+            )
+        })
+    }
+}
+
+impl Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Zone::Local => write!(f, "local"),
+            Zone::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+impl Serialize for Zone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instant(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("local".parse::<Zone>().unwrap(), Zone::Local);
+        assert_eq!(
+            "Europe/Prague".parse::<Zone>().unwrap(),
+            Zone::Named(chrono_tz::Europe::Prague)
+        );
+        assert!("Mars/Olympus".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn test_date_of_around_midnight() {
+        let late_evening_utc = instant("2024-01-01T23:30:00Z");
+
+        let prague = Zone::Named(chrono_tz::Europe::Prague);
+        let new_york = Zone::Named(chrono_tz::America::New_York);
+
+        assert_eq!(
+            prague.date_of(&late_evening_utc),
+            NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+        );
+        assert_eq!(
+            new_york.date_of(&late_evening_utc),
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let zone = Zone::Named(chrono_tz::Asia::Tokyo);
+
+        assert_eq!(
+            zone.format(&instant("2024-01-01T20:15:00Z"), "%Y-%m-%d %H:%M"),
+            "2024-01-02 05:15"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::NoteSearchArgs,
+    auth::OAuthSettings,
+    model::{
+        Account, DeviceAuthorization, DeviceTokenResponse, GetNotesResponse, Note,
+        TokenPollResponse,
+    },
+    utils::{date::zone::Zone, fs::write_atomic},
+};
+
+use super::{web::SearchRequestDate, Client, ServerHealth};
+
+pub const DEFAULT_STORE_FILENAME: &str = "notes.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LocalStore {
+    next_id: i64,
+    notes: Vec<Note>,
+}
+
+/// Client keeping notes in a file next to the profile instead of talking to a server
+pub struct LocalClient {
+    store_path: PathBuf,
+    zone: Zone,
+}
+
+impl LocalClient {
+    pub fn new(store_path: &Path, zone: Zone) -> Self {
+        Self {
+            store_path: store_path.to_path_buf(),
+            zone,
+        }
+    }
+
+    /// Stores a copy of a note coming from elsewhere, keeping its timestamps.
+    /// Replaces the note with `id` or creates a new one when `id` is `None`.
+    pub fn put_note(&mut self, id: Option<i64>, note: &Note) -> anyhow::Result<Note> {
+        let mut store = self.load()?;
+
+        let id = match id {
+            Some(id) => id,
+            None => {
+                store.next_id += 1;
+                store.next_id - 1
+            }
+        };
+
+        let note = Note {
+            id: Some(id),
+            ..note.clone()
+        };
+
+        match store.notes.iter_mut().find(|n| n.id == Some(id)) {
+            Some(existing) => *existing = note.clone(),
+            None => store.notes.push(note.clone()),
+        }
+
+        self.save(&store)?;
+
+        Ok(note)
+    }
+
+    fn load(&self) -> anyhow::Result<LocalStore> {
+        if !self.store_path.exists() {
+            return Ok(LocalStore {
+                next_id: 1,
+                notes: vec![],
+            });
+        }
+
+        let content =
+            std::fs::read_to_string(&self.store_path).context("Failed to read note store")?;
+
+        serde_json::from_str(&content).context("Failed to parse note store")
+    }
+
+    fn save(&self, store: &LocalStore) -> anyhow::Result<()> {
+        let content = serde_json::to_string_pretty(store).context("Failed to serialize notes")?;
+        write_atomic(&self.store_path, content.as_bytes()).context("Failed to write note store")?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Client for LocalClient {
+    async fn ping(&self) -> anyhow::Result<Option<Account>> {
+        Ok(None)
+    }
+
+    async fn health(&self) -> anyhow::Result<ServerHealth> {
+        Ok(ServerHealth {
+            round_trip: Duration::ZERO,
+            server_time: None,
+        })
+    }
+
+    async fn revoke_token(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn send_device_code(&self, _device_code: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Local backend doesn't support login")
+    }
+
+    async fn poll_for_token(&mut self, _device_code: &str) -> anyhow::Result<TokenPollResponse> {
+        anyhow::bail!("Local backend doesn't support login")
+    }
+
+    async fn authorize_device(
+        &self,
+        _oauth: &OAuthSettings,
+    ) -> anyhow::Result<DeviceAuthorization> {
+        anyhow::bail!("Local backend doesn't support login")
+    }
+
+    async fn poll_device_token(
+        &self,
+        _oauth: &OAuthSettings,
+        _device_code: &str,
+    ) -> anyhow::Result<DeviceTokenResponse> {
+        anyhow::bail!("Local backend doesn't support login")
+    }
+
+    async fn create_note(
+        &mut self,
+        content: String,
+        tags: Vec<String>,
+        date: NaiveDate,
+    ) -> anyhow::Result<Note> {
+        let mut store = self.load()?;
+        let now = Utc::now();
+
+        let note = Note {
+            id: Some(store.next_id),
+            content,
+            tags,
+            created_at: now,
+            updated_at: now,
+            target_date: Some(date),
+        };
+
+        store.next_id += 1;
+        store.notes.push(note.clone());
+        self.save(&store)?;
+
+        Ok(note)
+    }
+
+    async fn update_note(
+        &mut self,
+        id: i64,
+        content: String,
+        tags: Vec<String>,
+        date: Option<NaiveDate>,
+    ) -> anyhow::Result<Note> {
+        let mut store = self.load()?;
+
+        let note = store
+            .notes
+            .iter_mut()
+            .find(|n| n.id == Some(id))
+            .ok_or_else(|| anyhow::anyhow!("Note #{} not found", id))?;
+
+        note.content = content;
+        note.tags = tags;
+        note.target_date = date;
+        note.updated_at = Utc::now();
+
+        let note = note.clone();
+        self.save(&store)?;
+
+        Ok(note)
+    }
+
+    async fn get_note(&mut self, id: i64) -> anyhow::Result<Note> {
+        self.load()?
+            .notes
+            .into_iter()
+            .find(|n| n.id == Some(id))
+            .ok_or_else(|| anyhow::anyhow!("Note #{} not found", id))
+    }
+
+    async fn get_notes(&mut self) -> anyhow::Result<GetNotesResponse> {
+        let notes = self.load()?.notes;
+
+        Ok(GetNotesResponse { notes })
+    }
+
+    async fn search(&mut self, args: &NoteSearchArgs) -> anyhow::Result<GetNotesResponse> {
+        let today = self.zone.today();
+
+        let target_date = args
+            .filter
+            .date
+            .as_ref()
+            .and_then(|d| d.search_for_day(today));
+        let created_at = args
+            .filter
+            .created
+            .as_ref()
+            .and_then(|d| d.search_for_day(today));
+        let updated_at = args
+            .filter
+            .updated
+            .as_ref()
+            .and_then(|d| d.search_for_day(today));
+        let term = args.filter.term.as_ref().map(|t| t.to_lowercase());
+
+        let mut notes: Vec<Note> = self
+            .load()?
+            .notes
+            .into_iter()
+            .filter(|n| {
+                term.as_ref()
+                    .is_none_or(|t| n.content.to_lowercase().contains(t))
+            })
+            .filter(|n| args.filter.tag.iter().all(|t| n.tags.contains(t)))
+            .filter(|n| date_matches(&target_date, n.target_date))
+            .filter(|n| date_matches(&created_at, Some(self.zone.date_of(&n.created_at))))
+            .filter(|n| date_matches(&updated_at, Some(self.zone.date_of(&n.updated_at))))
+            .collect();
+
+        // Newest first, so that a limit of one returns the latest note
+        notes.sort_by_key(|n| std::cmp::Reverse(n.created_at));
+
+        if let Some(limit) = args.filter.limit {
+            notes.truncate(limit.max(0) as usize);
+        }
+
+        Ok(GetNotesResponse { notes })
+    }
+
+    fn get_server_url(&self) -> String {
+        self.store_path.to_string_lossy().into_owned()
+    }
+
+    async fn delete(&self, ids: &[i64]) -> anyhow::Result<()> {
+        let mut store = self.load()?;
+
+        if let Some(id) = ids
+            .iter()
+            .find(|id| !store.notes.iter().any(|n| n.id == Some(**id)))
+        {
+            anyhow::bail!("Note #{} not found", id);
+        }
+
+        store
+            .notes
+            .retain(|n| n.id.is_none_or(|id| !ids.contains(&id)));
+        self.save(&store)?;
+
+        Ok(())
+    }
+}
+
+pub(super) fn date_matches(filter: &Option<SearchRequestDate>, date: Option<NaiveDate>) -> bool {
+    match (filter, date) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(SearchRequestDate::Single(expected)), Some(date)) => *expected == date,
+        (Some(SearchRequestDate::Range { from, until }), Some(date)) => {
+            from.is_none_or(|from| date >= from) && until.is_none_or(|until| date <= until)
+        }
+    }
+}
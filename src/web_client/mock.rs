@@ -1,28 +1,108 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
 use async_trait::async_trait;
 use chrono::{NaiveDate, Utc};
 
 use crate::{
     args::NoteSearchArgs,
-    model::{GetNotesResponse, Note},
+    auth::OAuthSettings,
+    model::{
+        Account, DeviceAuthorization, DeviceTokenResponse, GetNotesResponse, Note, Token,
+        TokenPollResponse,
+    },
 };
 
-use super::Client;
+use super::{local::date_matches, Client, ClientError, ServerHealth};
+
+/// Mock parameter simulating a server that can't be reached
+pub const MOCK_PARAM_OFFLINE: &str = "offline";
+/// Mock parameter simulating a server that answers reads, but drops the connection on writes
+pub const MOCK_PARAM_WRITES_OFFLINE: &str = "writes_offline";
+/// Mock parameter simulating a server that rejects the token
+pub const MOCK_PARAM_UNAUTHORIZED: &str = "unauthorized";
+/// Mock parameter scripting the answers of the device grant token endpoint, e.g.
+/// `poll:pending,slow_down,success`. Once the script runs out, the login stays pending.
+pub const MOCK_PARAM_POLL_PREFIX: &str = "poll:";
+/// Mock parameter removing notes from the mocked server, e.g. `deleted:2,3`
+pub const MOCK_PARAM_DELETED_PREFIX: &str = "deleted:";
+/// Mock parameter removing notes from the mocked server right after they were read, as if
+/// another device deleted them meanwhile, e.g. `deleted_after_read:2`
+pub const MOCK_PARAM_DELETED_AFTER_READ_PREFIX: &str = "deleted_after_read:";
+/// Mock parameter simulating a token that is accepted, but may not delete notes
+pub const MOCK_PARAM_NO_DELETE: &str = "no_delete";
+/// Mock parameter simulating a token that is accepted, but may not create notes
+pub const MOCK_PARAM_NO_CREATE: &str = "no_create";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct MockClient {
     response_counter: u16,
+    offline: bool,
+    writes_offline: bool,
+    unauthorized: bool,
+    no_delete: bool,
+    no_create: bool,
+    poll_script: Option<Vec<String>>,
+    polls: AtomicUsize,
+    deleted: Vec<i64>,
+    deleted_after_read: Vec<i64>,
 }
 
 impl MockClient {
-    pub fn new() -> Self {
+    pub fn new(param: Option<&str>) -> Self {
         Self {
             response_counter: 0,
+            offline: param == Some(MOCK_PARAM_OFFLINE),
+            writes_offline: param == Some(MOCK_PARAM_WRITES_OFFLINE),
+            unauthorized: param == Some(MOCK_PARAM_UNAUTHORIZED),
+            no_delete: param == Some(MOCK_PARAM_NO_DELETE),
+            no_create: param == Some(MOCK_PARAM_NO_CREATE),
+            poll_script: param
+                .and_then(|p| p.strip_prefix(MOCK_PARAM_POLL_PREFIX))
+                .map(|script| script.split(',').map(|s| s.trim().to_string()).collect()),
+            polls: AtomicUsize::new(0),
+            deleted: parse_ids(param, MOCK_PARAM_DELETED_PREFIX),
+            deleted_after_read: parse_ids(param, MOCK_PARAM_DELETED_AFTER_READ_PREFIX),
+        }
+    }
+
+    fn check_online(&self) -> anyhow::Result<()> {
+        if self.offline {
+            return Err(ClientError::Unreachable("mocked offline server".to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    fn check_writable(&self) -> anyhow::Result<()> {
+        self.check_online()?;
+
+        if self.writes_offline {
+            return Err(ClientError::Unreachable("mocked connection drop".to_string()).into());
         }
+
+        Ok(())
     }
 }
 
+fn parse_ids(param: Option<&str>, prefix: &str) -> Vec<i64> {
+    param
+        .and_then(|p| p.strip_prefix(prefix))
+        .map(|ids| {
+            ids.split(',')
+                .filter_map(|id| id.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 pub const MOCK_URL: &str = "mocked_instance";
 pub const MOCK_TOKEN: &str = "mocked_token";
+pub const MOCK_USER_CODE: &str = "WDJB-MJHT";
+pub const MOCK_ACCOUNT: &str = "mock-user";
+pub const MOCK_POLL_INTERVAL: u64 = 1;
 
 #[async_trait]
 impl Client for MockClient {
@@ -31,26 +111,70 @@ impl Client for MockClient {
         Ok(())
     }
 
-    async fn poll_for_token(
-        &mut self,
-        device_code: &str,
-    ) -> anyhow::Result<crate::model::TokenPollResponse> {
-        self.response_counter = 0;
-
+    async fn poll_for_token(&mut self, device_code: &str) -> anyhow::Result<TokenPollResponse> {
         println!(
             "Mocking polling for token with device code: {}",
             device_code
         );
 
         if self.response_counter == 1 {
-            return Ok(crate::model::TokenPollResponse::Success(
-                MOCK_TOKEN.to_string(),
-            ));
+            return Ok(TokenPollResponse::Success(Token {
+                access_token: MOCK_TOKEN.to_string(),
+                expires_in: None,
+            }));
         }
 
         self.response_counter += 1;
 
-        Ok(crate::model::TokenPollResponse::Pending)
+        Ok(TokenPollResponse::Pending)
+    }
+
+    async fn authorize_device(&self, oauth: &OAuthSettings) -> anyhow::Result<DeviceAuthorization> {
+        self.check_online()?;
+        println!(
+            "Mocking device authorization for client: {}",
+            oauth.client_id
+        );
+
+        Ok(DeviceAuthorization {
+            device_code: "mocked_device_code".to_string(),
+            user_code: MOCK_USER_CODE.to_string(),
+            verification_uri: format!("{}/device", MOCK_URL),
+            verification_uri_complete: None,
+            interval: MOCK_POLL_INTERVAL,
+            expires_in: 60,
+        })
+    }
+
+    async fn poll_device_token(
+        &self,
+        _oauth: &OAuthSettings,
+        device_code: &str,
+    ) -> anyhow::Result<DeviceTokenResponse> {
+        println!(
+            "Mocking polling token endpoint with device code: {}",
+            device_code
+        );
+
+        let Some(script) = &self.poll_script else {
+            return Ok(DeviceTokenResponse::Success(Token {
+                access_token: MOCK_TOKEN.to_string(),
+                expires_in: Some(3600),
+            }));
+        };
+
+        let poll = self.polls.fetch_add(1, Ordering::SeqCst);
+        Ok(match script.get(poll).map(String::as_str) {
+            None | Some("pending") => DeviceTokenResponse::Pending,
+            Some("slow_down") => DeviceTokenResponse::SlowDown,
+            Some("access_denied") => DeviceTokenResponse::Denied,
+            Some("expired_token") => DeviceTokenResponse::Expired,
+            Some("success") => DeviceTokenResponse::Success(Token {
+                access_token: MOCK_TOKEN.to_string(),
+                expires_in: Some(3600),
+            }),
+            Some(other) => DeviceTokenResponse::Failure(other.to_string()),
+        })
     }
 
     async fn create_note(
@@ -59,6 +183,14 @@ impl Client for MockClient {
         _tags: Vec<String>,
         date: NaiveDate,
     ) -> anyhow::Result<crate::model::Note> {
+        self.check_writable()?;
+
+        if self.no_create {
+            return Err(
+                ClientError::Unauthorized("server answered 403 Forbidden".to_string()).into(),
+            );
+        }
+
         let note = crate::model::Note {
             id: Some(1),
             content,
@@ -71,10 +203,59 @@ impl Client for MockClient {
         Ok(note)
     }
 
-    async fn ping(&self) -> anyhow::Result<()> {
+    async fn update_note(
+        &mut self,
+        id: i64,
+        content: String,
+        tags: Vec<String>,
+        date: Option<NaiveDate>,
+    ) -> anyhow::Result<crate::model::Note> {
+        self.check_writable()?;
+
+        let existing = self.get_note(id).await?;
+
+        let note = crate::model::Note {
+            id: Some(id),
+            content,
+            tags,
+            created_at: existing.created_at,
+            updated_at: chrono::Utc::now(),
+            target_date: date,
+        };
+
+        Ok(note)
+    }
+
+    async fn ping(&self) -> anyhow::Result<Option<Account>> {
+        self.check_online()?;
+
+        if self.unauthorized {
+            return Err(ClientError::Unauthorized("mocked expired token".to_string()).into());
+        }
+
+        Ok(Some(Account {
+            name: MOCK_ACCOUNT.to_string(),
+        }))
+    }
+
+    async fn revoke_token(&self) -> anyhow::Result<()> {
+        self.check_online()?;
+
+        // Printed so that tests can tell the server was asked
+        println!("Mock server: token revoked");
+
         Ok(())
     }
 
+    async fn health(&self) -> anyhow::Result<ServerHealth> {
+        self.check_online()?;
+
+        Ok(ServerHealth {
+            round_trip: Duration::from_millis(3),
+            server_time: Some(Utc::now()),
+        })
+    }
+
     #[allow(clippy::unwrap_used)]
     async fn get_notes(&mut self) -> anyhow::Result<GetNotesResponse> {
         let notes = vec![
@@ -117,16 +298,53 @@ impl Client for MockClient {
                         .to_string(),
                 target_date: Some(Utc::now().naive_utc().date()),
             },
-        ];
+        ]
+        .into_iter()
+        .filter(|n| n.id.is_none_or(|id| !self.deleted.contains(&id)))
+        .collect();
 
         Ok(GetNotesResponse { notes })
     }
 
+    async fn get_note(&mut self, id: i64) -> anyhow::Result<Note> {
+        let note = self
+            .get_notes()
+            .await?
+            .notes
+            .into_iter()
+            .find(|n| n.id == Some(id))
+            .ok_or_else(|| anyhow::anyhow!("Note #{} not found", id))?;
+
+        if self.deleted_after_read.contains(&id) {
+            self.deleted.push(id);
+        }
+
+        Ok(note)
+    }
+
+    /// Only the update date is filtered, the sync asks for the notes changed since its last run
     async fn search(
         &mut self,
-        _args: &NoteSearchArgs,
+        args: &NoteSearchArgs,
     ) -> anyhow::Result<crate::model::GetNotesResponse> {
-        Ok(self.get_notes().await?)
+        let updated_at = args
+            .filter
+            .updated
+            .as_ref()
+            .and_then(|d| d.search_for_day(Utc::now().date_naive()));
+
+        let mut notes: Vec<Note> = self
+            .get_notes()
+            .await?
+            .notes
+            .into_iter()
+            .filter(|n| date_matches(&updated_at, Some(n.updated_at.date_naive())))
+            .collect();
+        if let Some(limit) = args.filter.limit {
+            notes.truncate(limit.max(0) as usize);
+        }
+
+        Ok(GetNotesResponse { notes })
     }
 
     fn get_server_url(&self) -> String {
@@ -134,6 +352,12 @@ impl Client for MockClient {
     }
 
     async fn delete(&self, _ids: &[i64]) -> anyhow::Result<()> {
+        if self.no_delete {
+            return Err(
+                ClientError::Unauthorized("server answered 403 Forbidden".to_string()).into(),
+            );
+        }
+
         Ok(())
     }
 }
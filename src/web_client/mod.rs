@@ -1,12 +1,19 @@
+use std::{fmt::Display, path::Path, time::Duration};
+
 use async_trait::async_trait;
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, Utc};
 
 use crate::{
-    app_config::AppConfig,
+    app_config::{AppConfig, Backend},
     args::NoteSearchArgs,
-    model::{GetNotesResponse, Note, TokenPollResponse},
+    auth::OAuthSettings,
+    model::{
+        Account, DeviceAuthorization, DeviceTokenResponse, GetNotesResponse, Note,
+        TokenPollResponse,
+    },
 };
 
+pub mod local;
 #[cfg(debug_assertions)]
 pub mod mock;
 
@@ -16,36 +23,113 @@ mod web;
 #[cfg(debug_assertions)]
 pub fn get_client(config: &AppConfig) -> Box<dyn Client> {
     use mock::MockClient;
-    use web::WebClient;
 
     if config.mock_server {
-        Box::new(MockClient::new())
+        Box::new(MockClient::new(config.mock_param.as_deref()))
     } else {
-        Box::new(WebClient::new(
-            config.server_url.clone(),
-            config.token.clone(),
-        ))
+        get_backend_client(config)
     }
 }
 
 #[cfg(not(debug_assertions))]
 pub fn get_client(config: &AppConfig) -> Box<dyn Client> {
+    get_backend_client(config)
+}
+
+fn get_backend_client(config: &AppConfig) -> Box<dyn Client> {
+    use local::LocalClient;
     use web::WebClient;
 
-    Box::new(WebClient::new(config.server_url.clone()))
+    match config.backend {
+        Backend::Web => Box::new(WebClient::new(
+            config.server_url.clone(),
+            config.token.clone(),
+            config.timezone,
+        )),
+        Backend::Local => Box::new(LocalClient::new(
+            Path::new(&config.store_path),
+            config.timezone,
+        )),
+    }
+}
+
+/// Client failures that callers react to, everything else is reported as is
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server could not be reached at all
+    Unreachable(String),
+    /// The token is missing, expired or was rejected by the server
+    Unauthorized(String),
+}
+
+impl ClientError {
+    pub fn is_unreachable(error: &anyhow::Error) -> bool {
+        matches!(
+            error.downcast_ref::<ClientError>(),
+            Some(ClientError::Unreachable(_))
+        )
+    }
+
+    pub fn is_unauthorized(error: &anyhow::Error) -> bool {
+        matches!(
+            error.downcast_ref::<ClientError>(),
+            Some(ClientError::Unauthorized(_))
+        )
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable(reason) => write!(f, "Server is unreachable: {}", reason),
+            Self::Unauthorized(reason) => write!(f, "Login is not valid: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Result of an unauthenticated request to the server
+#[derive(Debug, Clone)]
+pub struct ServerHealth {
+    pub round_trip: Duration,
+    /// Time from the `Date` header of the response, if the server sent one
+    pub server_time: Option<DateTime<Utc>>,
 }
 
 #[async_trait]
 pub trait Client {
-    async fn ping(&self) -> anyhow::Result<()>;
+    /// Checks the token, returns the account it belongs to when the server names it
+    async fn ping(&self) -> anyhow::Result<Option<Account>>;
+    /// Checks that the server answers at all, without needing a token
+    async fn health(&self) -> anyhow::Result<ServerHealth>;
     async fn send_device_code(&self, device_code: &str) -> anyhow::Result<()>;
+    /// Invalidates the token on the server, a token the server no longer knows or a server
+    /// without the revoke endpoint counts as revoked
+    async fn revoke_token(&self) -> anyhow::Result<()>;
     async fn poll_for_token(&mut self, device_code: &str) -> anyhow::Result<TokenPollResponse>;
+    /// Starts an RFC 8628 device authorization at the identity provider
+    async fn authorize_device(&self, oauth: &OAuthSettings) -> anyhow::Result<DeviceAuthorization>;
+    /// Asks the token endpoint of the identity provider whether the user finished the login
+    async fn poll_device_token(
+        &self,
+        oauth: &OAuthSettings,
+        device_code: &str,
+    ) -> anyhow::Result<DeviceTokenResponse>;
     async fn create_note(
         &mut self,
         content: String,
         tags: Vec<String>,
         date: NaiveDate,
     ) -> anyhow::Result<Note>;
+    async fn update_note(
+        &mut self,
+        id: i64,
+        content: String,
+        tags: Vec<String>,
+        date: Option<NaiveDate>,
+    ) -> anyhow::Result<Note>;
+    async fn get_note(&mut self, id: i64) -> anyhow::Result<Note>;
     async fn get_notes(&mut self) -> anyhow::Result<GetNotesResponse>;
     async fn search(&mut self, args: &NoteSearchArgs) -> anyhow::Result<GetNotesResponse>;
     async fn delete(&self, ids: &[i64]) -> anyhow::Result<()>;
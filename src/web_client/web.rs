@@ -1,38 +1,77 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
 use async_trait::async_trait;
-use chrono::{NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
 use serde_json::json;
 
 use crate::{
     args::NoteSearchArgs,
-    model::{DeviceCodeRequest, GetNotesResponse, Note, Token, TokenPollResponse},
-    utils::date::date_filter::DateFilter,
+    auth::OAuthSettings,
+    model::{
+        Account, DeviceAuthorization, DeviceCodeRequest, DeviceTokenResponse, GetNotesResponse,
+        Note, OAuthError, Token, TokenPollResponse,
+    },
+    utils::date::{date_filter::DateFilter, zone::Zone},
 };
 
-use super::Client;
+use super::{Client, ClientError, ServerHealth};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Limit for a whole request, so a stalled server doesn't hang the command
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
 
 pub struct WebClient {
     server_url: String,
     token: Option<String>,
+    zone: Zone,
     client: reqwest::Client,
 }
 
 impl WebClient {
-    pub fn new(server_url: String, token: Option<String>) -> Self {
+    pub fn new(server_url: String, token: Option<String>, zone: Zone) -> Self {
         Self {
             server_url,
             token,
-            client: reqwest::Client::new(),
+            zone,
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
         }
     }
 }
 
+/// Connection failures are reported as `ClientError::Unreachable` so notes can be queued.
+/// Timeouts after connecting are not, the server may have stored the note already.
+fn send_error(error: reqwest::Error) -> anyhow::Error {
+    if error.is_connect() {
+        ClientError::Unreachable(error.to_string()).into()
+    } else {
+        error.into()
+    }
+}
+
+/// Rejected tokens are reported as `ClientError::Unauthorized` so that the user can be asked
+/// to log in again
+fn check_authorized(response: &reqwest::Response) -> Result<(), ClientError> {
+    match response.status() {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(
+            ClientError::Unauthorized(format!("server answered {}", response.status())),
+        ),
+        _ => Ok(()),
+    }
+}
+
 #[async_trait]
 impl Client for WebClient {
-    async fn ping(&self) -> anyhow::Result<()> {
+    async fn ping(&self) -> anyhow::Result<Option<Account>> {
         let real_token = match self.token {
             Some(ref token) => token,
-            None => anyhow::bail!("No token available"),
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
         };
 
         let response = self
@@ -40,12 +79,74 @@ impl Client for WebClient {
             .get(format!("{}/health/auth", self.server_url))
             .header("Authorization", format!("Bearer {}", real_token))
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
 
         if !response.status().is_success() {
             anyhow::bail!("Cannot verify login");
         }
 
+        // Older servers answer without naming the account
+        Ok(response.json().await.ok())
+    }
+
+    async fn health(&self) -> anyhow::Result<ServerHealth> {
+        let started = Instant::now();
+
+        // Any response means the server is up, the status doesn't matter here
+        let response = self
+            .client
+            .get(format!("{}/health", self.server_url))
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        let round_trip = started.elapsed();
+        let server_time = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|d| d.to_str().ok())
+            .and_then(|d| DateTime::parse_from_rfc2822(d).ok())
+            .map(|d| d.with_timezone(&Utc));
+
+        Ok(ServerHealth {
+            round_trip,
+            server_time,
+        })
+    }
+
+    async fn revoke_token(&self) -> anyhow::Result<()> {
+        let real_token = match self.token {
+            Some(ref token) => token,
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/auth/revoke", self.server_url))
+            .bearer_auth(real_token)
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        // Servers without the endpoint answer 404 or 405, they don't keep tokens to revoke
+        if !response.status().is_success()
+            && !matches!(
+                response.status(),
+                reqwest::StatusCode::UNAUTHORIZED
+                    | reqwest::StatusCode::NOT_FOUND
+                    | reqwest::StatusCode::METHOD_NOT_ALLOWED
+            )
+        {
+            anyhow::bail!(
+                "Failed to revoke token, {}: {}",
+                response.status(),
+                response.text().await?
+            );
+        }
+
         Ok(())
     }
 
@@ -59,7 +160,8 @@ impl Client for WebClient {
             .post(format!("{}/auth/device", self.server_url))
             .json(&data)
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
 
         if !response.status().is_success() {
             anyhow::bail!("Failed to register device");
@@ -73,18 +175,81 @@ impl Client for WebClient {
             .client
             .get(format!("{}/auth/status/{}", self.server_url, device_code))
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
 
         match response.status() {
             reqwest::StatusCode::OK => {
                 let token: Token = response.json().await?;
-                Ok(TokenPollResponse::Success(token.access_token))
+                Ok(TokenPollResponse::Success(token))
             }
             reqwest::StatusCode::ACCEPTED => Ok(TokenPollResponse::Pending),
             _ => anyhow::bail!("Authentication polling failed: {}", response.status()),
         }
     }
 
+    async fn authorize_device(&self, oauth: &OAuthSettings) -> anyhow::Result<DeviceAuthorization> {
+        let mut form = vec![("client_id", oauth.client_id.as_str())];
+        if let Some(scope) = &oauth.scope {
+            form.push(("scope", scope));
+        }
+
+        let response = self
+            .client
+            .post(&oauth.device_authorization_url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Device authorization failed, {}: {}",
+                response.status(),
+                response.text().await?
+            );
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn poll_device_token(
+        &self,
+        oauth: &OAuthSettings,
+        device_code: &str,
+    ) -> anyhow::Result<DeviceTokenResponse> {
+        let response = self
+            .client
+            .post(&oauth.token_url)
+            .form(&[
+                ("grant_type", DEVICE_CODE_GRANT_TYPE),
+                ("device_code", device_code),
+                ("client_id", &oauth.client_id),
+            ])
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        if response.status().is_success() {
+            let token: Token = response.json().await?;
+            return Ok(DeviceTokenResponse::Success(token));
+        }
+
+        let status = response.status();
+        let error: OAuthError = response
+            .json()
+            .await
+            .with_context(|| format!("Token request failed: {}", status))?;
+
+        Ok(match error.error.as_str() {
+            "authorization_pending" => DeviceTokenResponse::Pending,
+            "slow_down" => DeviceTokenResponse::SlowDown,
+            "access_denied" => DeviceTokenResponse::Denied,
+            "expired_token" => DeviceTokenResponse::Expired,
+            _ => DeviceTokenResponse::Failure(error.error_description.unwrap_or(error.error)),
+        })
+    }
+
     async fn create_note(
         &mut self,
         content: String,
@@ -93,7 +258,7 @@ impl Client for WebClient {
     ) -> anyhow::Result<Note> {
         let real_token = match self.token {
             Some(ref token) => token,
-            None => anyhow::bail!("No token available"),
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
         };
 
         let response = self
@@ -107,7 +272,10 @@ impl Client for WebClient {
                 "target_date": date
             }))
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
 
         if !response.status().is_success() {
             println!("{:?}", response.text().await);
@@ -126,10 +294,76 @@ impl Client for WebClient {
         })
     }
 
+    async fn update_note(
+        &mut self,
+        id: i64,
+        content: String,
+        tags: Vec<String>,
+        date: Option<NaiveDate>,
+    ) -> anyhow::Result<Note> {
+        let real_token = match self.token {
+            Some(ref token) => token,
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
+        };
+
+        let response = self
+            .client
+            .put(format!("{}/note/{}", self.server_url, id))
+            .header("Content-Type", "application/json")
+            .bearer_auth(real_token)
+            .json(&json!({
+                "content": content,
+                "tags": tags,
+                "target_date": date
+            }))
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to update note, {}", response.text().await?);
+        }
+
+        let note = response.json::<Note>().await?;
+
+        Ok(note)
+    }
+
+    async fn get_note(&mut self, id: i64) -> anyhow::Result<Note> {
+        let real_token = match self.token {
+            Some(ref token) => token,
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
+        };
+
+        let response = self
+            .client
+            .get(format!("{}/note/{}", self.server_url, id))
+            .bearer_auth(real_token)
+            .send()
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Note #{} not found", id);
+        }
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to get note, {}", response.text().await?);
+        }
+
+        let note = response.json::<Note>().await?;
+
+        Ok(note)
+    }
+
     async fn get_notes(&mut self) -> anyhow::Result<GetNotesResponse> {
         let real_token = match self.token {
             Some(ref token) => token,
-            None => anyhow::bail!("No token available"),
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
         };
 
         let response = self
@@ -137,7 +371,10 @@ impl Client for WebClient {
             .get(format!("{}/note", self.server_url))
             .header("Authorization", format!("Bearer {}", real_token))
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
 
         if !response.status().is_success() {
             anyhow::bail!("Failed to get notes");
@@ -156,28 +393,33 @@ impl Client for WebClient {
     async fn search(&mut self, args: &NoteSearchArgs) -> anyhow::Result<GetNotesResponse> {
         let real_token = match self.token {
             Some(ref token) => token,
-            None => anyhow::bail!("No token available"),
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
         };
 
+        let today = self.zone.today();
+
         let target_date = args
+            .filter
             .date
             .clone()
-            .and_then(|d| d.search_for_day(Utc::now().date_naive()));
+            .and_then(|d| d.search_for_day(today));
 
         let created_at = args
+            .filter
             .created
             .clone()
-            .and_then(|d| d.search_for_day(Utc::now().date_naive()));
+            .and_then(|d| d.search_for_day(today));
 
         let updated_at = args
+            .filter
             .updated
             .clone()
-            .and_then(|d| d.search_for_day(Utc::now().date_naive()));
+            .and_then(|d| d.search_for_day(today));
 
         let body = SearchRequest {
-            tag: args.tag.clone(),
-            term: args.term.clone(),
-            limit: args.limit,
+            tag: args.filter.tag.clone(),
+            term: args.filter.term.clone(),
+            limit: args.filter.limit,
             target_date,
             created_at,
             updated_at,
@@ -190,7 +432,10 @@ impl Client for WebClient {
             .bearer_auth(real_token)
             .header("Content-Type", "application/json")
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
 
         if !response.status().is_success() {
             anyhow::bail!("Failed to seaarch for notes, {}", response.text().await?);
@@ -208,17 +453,28 @@ impl Client for WebClient {
     async fn delete(&self, ids: &[i64]) -> anyhow::Result<()> {
         let real_token = match self.token {
             Some(ref token) => token,
-            None => anyhow::bail!("No token available"),
+            None => return Err(ClientError::Unauthorized("not logged in".to_string()).into()),
         };
 
-        let _response = self
+        let response = self
             .client
             .post(format!("{}/note/delete", self.server_url))
             .json(ids)
             .bearer_auth(real_token)
             .header("Content-Type", "application/json")
             .send()
-            .await?;
+            .await
+            .map_err(send_error)?;
+
+        check_authorized(&response)?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Failed to delete notes, {}: {}",
+                response.status(),
+                response.text().await?
+            );
+        }
 
         Ok(())
     }